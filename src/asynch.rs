@@ -0,0 +1,344 @@
+// Async driver on embedded-hal-async, sharing the register map, config
+// encoding and sample decoding with the blocking `Lis2mdl`.
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::i2c::{I2c, Operation as I2cOperation};
+use embedded_hal_async::spi::{Operation as SpiOperation, SpiDevice};
+
+use crate::fixed::raw_to_nanotesla;
+use crate::interface::{I2C_CFG_REG_C, I2cInterface, SpiInterface, SpiWires, spi_address};
+use crate::register::{
+    CHIP_ID, CfgRegA, CfgRegB, CfgRegC, Mode, Register, RegisterBits, WritableRegister,
+};
+use crate::{
+    Address, BOOT_TIME_MS, Config, Error, Measurement, Orientation, SOFT_RESET_TIME_US, decode_xyz,
+};
+#[cfg(feature = "float")]
+use crate::{raw_to_celsius, raw_to_microtesla};
+
+/// Async counterpart of [`Interface`](crate::Interface).
+#[allow(async_fn_in_trait)]
+pub trait AsyncInterface {
+    type Error;
+
+    async fn write_registers(&mut self, reg: u8, data: &[u8]) -> Result<(), Error<Self::Error>>;
+    async fn read_registers(
+        &mut self,
+        reg: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), Error<Self::Error>>;
+
+    /// `CFG_REG_C` bits that select this interface on the device.
+    fn cfg_reg_c(&self) -> CfgRegC;
+}
+
+impl<I2C, E> AsyncInterface for I2cInterface<I2C>
+where
+    I2C: I2c<Error = E>,
+{
+    type Error = E;
+
+    async fn write_registers(&mut self, reg: u8, data: &[u8]) -> Result<(), Error<E>> {
+        let mut operations = [I2cOperation::Write(&[reg]), I2cOperation::Write(data)];
+        self.i2c
+            .transaction(self.address, &mut operations)
+            .await
+            .map_err(Error::I2C)
+    }
+
+    async fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        let mut operations = [I2cOperation::Write(&[reg]), I2cOperation::Read(buffer)];
+        self.i2c
+            .transaction(self.address, &mut operations)
+            .await
+            .map_err(Error::I2C)
+    }
+
+    fn cfg_reg_c(&self) -> CfgRegC {
+        I2C_CFG_REG_C
+    }
+}
+
+impl<SPI, E> AsyncInterface for SpiInterface<SPI>
+where
+    SPI: SpiDevice<Error = E>,
+{
+    type Error = E;
+
+    async fn write_registers(&mut self, reg: u8, data: &[u8]) -> Result<(), Error<E>> {
+        let mut operations = [
+            SpiOperation::Write(&[spi_address(reg, false)]),
+            SpiOperation::Write(data),
+        ];
+        self.spi
+            .transaction(&mut operations)
+            .await
+            .map_err(Error::Spi)
+    }
+
+    async fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        let mut operations = [
+            SpiOperation::Write(&[spi_address(reg, true)]),
+            SpiOperation::Read(buffer),
+        ];
+        self.spi
+            .transaction(&mut operations)
+            .await
+            .map_err(Error::Spi)
+    }
+
+    fn cfg_reg_c(&self) -> CfgRegC {
+        self.wires.cfg_reg_c()
+    }
+}
+
+#[derive(Debug)]
+pub struct Lis2mdlAsync<IFACE, DELAY> {
+    pub(crate) iface: IFACE,
+    pub(crate) delay: DELAY,
+    pub(crate) config: Config,
+    pub mag_x: i16,
+    pub mag_y: i16,
+    pub mag_z: i16,
+    pub(crate) orientation: Orientation,
+}
+
+impl<I2C, DELAY> Lis2mdlAsync<I2cInterface<I2C>, DELAY> {
+    pub fn new<A: Into<Address>>(i2c: I2C, address: A, delay: DELAY) -> Self {
+        Self::with_interface(I2cInterface::new(i2c, address), delay)
+    }
+}
+
+impl<SPI, DELAY> Lis2mdlAsync<SpiInterface<SPI>, DELAY> {
+    pub fn new_spi(spi: SPI, wires: SpiWires, delay: DELAY) -> Self {
+        Self::with_interface(SpiInterface::new(spi, wires), delay)
+    }
+}
+
+impl<IFACE, DELAY> Lis2mdlAsync<IFACE, DELAY> {
+    pub fn with_interface(iface: IFACE, delay: DELAY) -> Self {
+        Lis2mdlAsync {
+            iface,
+            delay,
+            config: Config::default(),
+            mag_x: 0,
+            mag_y: 0,
+            mag_z: 0,
+            orientation: Orientation::IDENTITY,
+        }
+    }
+
+    /// Use `config` instead of the default when `start` is called.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// How the chip is mounted relative to the device frame. Applied to
+    /// `current_xyz` and `current_xyz_nanotesla`; `mag_x/y/z` stay raw.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    pub fn release(self) -> (IFACE, DELAY) {
+        (self.iface, self.delay)
+    }
+
+    /// The configuration last applied with `configure` (or the default).
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    #[cfg(feature = "float")]
+    pub fn current_xyz(&mut self) -> (f32, f32, f32) {
+        let (x, y, z) = self
+            .orientation
+            .apply_raw((self.mag_x, self.mag_y, self.mag_z));
+        let x = raw_to_microtesla(x);
+        let y = raw_to_microtesla(y);
+        let z = raw_to_microtesla(z);
+
+        (x, y, z)
+    }
+
+    /// The last sample in nT, in the device frame. Divide by 100 for mG.
+    pub fn current_xyz_nanotesla(&self) -> (i32, i32, i32) {
+        let (x, y, z) = self
+            .orientation
+            .apply_raw((self.mag_x, self.mag_y, self.mag_z));
+
+        (
+            raw_to_nanotesla(x),
+            raw_to_nanotesla(y),
+            raw_to_nanotesla(z),
+        )
+    }
+}
+
+impl<IFACE, DELAY, E> Lis2mdlAsync<IFACE, DELAY>
+where
+    DELAY: DelayNs,
+    IFACE: AsyncInterface<Error = E>,
+{
+    pub async fn start(&mut self) -> Result<(), Error<E>> {
+        // same sequence as the blocking driver
+        self.probe().await?;
+
+        let interface = self.iface.cfg_reg_c();
+        self.write_reg(interface.with_bdu(true).with_drdy_on_pin(true))
+            .await?;
+        self.delay.delay_ns(5000).await;
+
+        self.configure(self.config).await
+    }
+
+    /// Check WHO_AM_I, then soft reset and reboot, then `start`.
+    pub async fn init(&mut self) -> Result<(), Error<E>> {
+        self.probe().await?;
+        self.reset().await?;
+        self.start().await
+    }
+
+    // Verify WHO_AM_I before any other write, except the 4WSPI select
+    async fn probe(&mut self) -> Result<(), Error<E>> {
+        let interface = self.iface.cfg_reg_c();
+        if interface.four_wire_spi() {
+            self.write_reg(interface).await?;
+            self.delay.delay_ns(5000).await;
+        }
+
+        self.verify_chip_id().await
+    }
+
+    pub async fn reset(&mut self) -> Result<(), Error<E>> {
+        let idle = CfgRegA::default().with_mode(Mode::Idle);
+
+        self.write_reg(idle.with_soft_rst(true)).await?;
+        self.delay.delay_us(SOFT_RESET_TIME_US).await;
+
+        self.write_reg(idle.with_reboot(true)).await?;
+        self.delay.delay_ms(BOOT_TIME_MS).await;
+
+        Ok(())
+    }
+
+    pub async fn verify_chip_id(&mut self) -> Result<(), Error<E>> {
+        match self.whoami().await? {
+            CHIP_ID => Ok(()),
+            id => Err(Error::WrongChipId(id)),
+        }
+    }
+
+    pub async fn configure(&mut self, config: Config) -> Result<(), Error<E>> {
+        self.write_reg(config.cfg_reg_b()).await?;
+        self.delay.delay_ns(5000).await;
+
+        self.write_reg(config.cfg_reg_a()).await?;
+        self.delay.delay_ns(10_000).await;
+
+        self.config = config;
+        Ok(())
+    }
+
+    pub async fn read_config(&mut self) -> Result<Config, Error<E>> {
+        let a = self.read_reg::<CfgRegA>().await?;
+        let b = self.read_reg::<CfgRegB>().await?;
+
+        Ok(Config::from_registers(a, b))
+    }
+
+    pub async fn whoami(&mut self) -> Result<u8, Error<E>> {
+        self.get_register(Register::WhoAmI.addr()).await
+    }
+
+    pub async fn get_register(&mut self, reg: u8) -> Result<u8, Error<E>> {
+        let mut buffer = [0u8; 1];
+        self.read_registers(reg, &mut buffer).await?;
+
+        Ok(buffer[0])
+    }
+
+    pub async fn set_register(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
+        self.iface.write_registers(reg, &[value]).await
+    }
+
+    pub async fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        self.iface.read_registers(reg, buffer).await
+    }
+
+    pub async fn read_reg<R: RegisterBits>(&mut self) -> Result<R, Error<E>> {
+        self.get_register(R::REGISTER.addr())
+            .await
+            .map(R::from_bits)
+    }
+
+    pub async fn write_reg<R: WritableRegister>(&mut self, value: R) -> Result<(), Error<E>> {
+        self.set_register(R::REGISTER.addr(), value.bits()).await
+    }
+
+    pub async fn modify_reg<R: WritableRegister, F: FnOnce(R) -> R>(
+        &mut self,
+        f: F,
+    ) -> Result<R, Error<E>> {
+        let value = f(self.read_reg::<R>().await?);
+        self.write_reg(value).await?;
+
+        Ok(value)
+    }
+
+    /// Read the status and output registers in one burst, leaving
+    /// `mag_x/y/z` untouched.
+    pub async fn read_measurement(&mut self) -> Result<Measurement, Error<E>> {
+        let mut buffer = [0u8; 7];
+        self.read_registers(Register::StatusReg.addr(), &mut buffer)
+            .await?;
+
+        Ok(Measurement::from_bytes(&buffer))
+    }
+
+    pub async fn read(&mut self) -> Result<(), Error<E>> {
+        let mut buffer = [0u8; 6];
+        self.read_registers(Register::OutxL.addr(), &mut buffer)
+            .await?;
+
+        (self.mag_x, self.mag_y, self.mag_z) = decode_xyz(&buffer);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "float")]
+    /// Die temperature in °C.
+    pub async fn read_temperature(&mut self) -> Result<f32, Error<E>> {
+        let mut buffer = [0u8; 2];
+        self.read_registers(Register::TempOutL.addr(), &mut buffer)
+            .await?;
+
+        Ok(raw_to_celsius(i16::from_le_bytes(buffer)))
+    }
+
+    #[cfg(feature = "float")]
+    /// Like `read`, but also returns the die temperature in °C from the
+    /// same burst (OUTX_L..TEMP_OUT_H).
+    pub async fn read_with_temperature(&mut self) -> Result<f32, Error<E>> {
+        let mut buffer = [0u8; 8];
+        self.read_registers(Register::OutxL.addr(), &mut buffer)
+            .await?;
+
+        (self.mag_x, self.mag_y, self.mag_z) = decode_xyz(&buffer);
+
+        Ok(raw_to_celsius(i16::from_le_bytes([buffer[6], buffer[7]])))
+    }
+
+    /// Wait for the INT/DRDY pin to signal a new sample, then read it.
+    ///
+    /// `start` routes data-ready to the pin (DRDY_on_PIN), active high.
+    pub async fn wait_for_data_ready<P: Wait>(&mut self, drdy: &mut P) -> Result<(), Error<E>> {
+        drdy.wait_for_high().await.map_err(|_| Error::Pin)?;
+        self.read().await
+    }
+}
@@ -0,0 +1,634 @@
+// Hard- and soft-iron calibration by least-squares ellipsoid fitting.
+//
+// Samples are folded into the normal equations of the general quadric
+//   a x² + b y² + c z² + 2d xy + 2e xz + 2f yz + 2g x + 2h y + 2i z = 1
+// so no sample buffer is needed. Math is done in f64 with local sqrt/cbrt,
+// since the micromath approximations are too coarse for a fit.
+#![allow(clippy::needless_range_loop)]
+
+use embedded_hal::delay::DelayNs;
+
+use crate::{Error, Interface, Lis2mdl};
+
+// Samples are scaled to roughly unit magnitude to keep the normal
+// equations well conditioned (Earth's field is 25-65 µT).
+const SCALE: f64 = 0.01;
+
+const PARAMS: usize = 9;
+
+/// Incremental ellipsoid fit over magnetometer samples in µT.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EllipsoidFit {
+    ata: [[f64; PARAMS]; PARAMS],
+    atb: [f64; PARAMS],
+    count: u32,
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+/// Result of [`EllipsoidFit::fit`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EllipsoidCalibration {
+    /// Ellipsoid center in µT, subtracted before the soft-iron correction
+    pub hard_iron: [f32; 3],
+    /// Symmetric matrix mapping the ellipsoid onto a sphere
+    pub soft_iron: [[f32; 3]; 3],
+    /// Radius of that sphere in µT, the local field strength
+    pub field_strength: f32,
+    /// RMS relative distance of the samples from the fitted surface
+    pub residual: f32,
+    /// Smallest per-axis fraction of the ellipsoid spanned by the samples
+    pub coverage: f32,
+}
+
+impl EllipsoidCalibration {
+    /// Apply hard- then soft-iron correction to a sample in µT.
+    pub fn apply(&self, xyz: (f32, f32, f32)) -> (f32, f32, f32) {
+        Calibration::from(*self).apply(xyz)
+    }
+}
+
+/// Whether [`Calibration`] is learning hard-iron offsets from new samples.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CalibrationState {
+    /// Offsets track the midpoint of the min/max seen on each axis
+    Collecting,
+    /// Offsets are fixed
+    #[default]
+    Frozen,
+}
+
+/// Hard-iron offsets and optional soft-iron matrix applied to samples in µT.
+///
+/// While collecting, every sample passed to `update` widens the per-axis
+/// min/max and the hard-iron offsets follow their midpoints. Nothing changes
+/// once frozen.
+///
+/// `hard_iron` is always the full offset, including any part the device
+/// already subtracts through its OFFSET registers (`device_offset`), so it
+/// can be stored and written back after the registers are cleared.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Calibration {
+    pub hard_iron: [f32; 3],
+    pub soft_iron: Option<[[f32; 3]; 3]>,
+    /// Die temperature in °C when the calibration was taken
+    pub temperature: Option<f32>,
+    /// Caller-defined timestamp or sequence number
+    pub sequence: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    state: CalibrationState,
+    // only meaningful while collecting, reset by `begin`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    min: [f32; 3],
+    #[cfg_attr(feature = "serde", serde(skip))]
+    max: [f32; 3],
+    // part of `hard_iron` held by the OFFSET registers, runtime only
+    #[cfg_attr(feature = "serde", serde(skip))]
+    device_offset: [f32; 3],
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<EllipsoidCalibration> for Calibration {
+    fn from(fit: EllipsoidCalibration) -> Self {
+        Calibration::new()
+            .with_hard_iron(fit.hard_iron)
+            .with_soft_iron(fit.soft_iron)
+    }
+}
+
+impl Calibration {
+    /// No correction, frozen.
+    pub const fn new() -> Self {
+        Calibration {
+            hard_iron: [0.0; 3],
+            soft_iron: None,
+            temperature: None,
+            sequence: 0,
+            state: CalibrationState::Frozen,
+            min: [f32::MAX; 3],
+            max: [f32::MIN; 3],
+            device_offset: [0.0; 3],
+        }
+    }
+
+    pub const fn with_hard_iron(mut self, hard_iron: [f32; 3]) -> Self {
+        self.hard_iron = hard_iron;
+        self
+    }
+
+    pub const fn with_soft_iron(mut self, soft_iron: [[f32; 3]; 3]) -> Self {
+        self.soft_iron = Some(soft_iron);
+        self
+    }
+
+    pub const fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub const fn with_sequence(mut self, sequence: u32) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub const fn state(&self) -> CalibrationState {
+        self.state
+    }
+
+    /// The part of `hard_iron` the device subtracts in hardware, set by
+    /// `Lis2mdl::store_hard_iron_offset`. Not stored by `to_bytes`.
+    pub const fn device_offset(&self) -> [f32; 3] {
+        self.device_offset
+    }
+
+    pub(crate) fn set_device_offset(&mut self, offset: [f32; 3]) {
+        self.device_offset = offset;
+    }
+
+    /// Start collecting min/max from scratch. The soft-iron matrix is kept.
+    pub fn begin(&mut self) {
+        self.min = [f32::MAX; 3];
+        self.max = [f32::MIN; 3];
+        self.state = CalibrationState::Collecting;
+    }
+
+    /// Freeze the current offsets.
+    pub fn finish(&mut self) {
+        self.state = CalibrationState::Frozen;
+    }
+
+    /// Drop all corrections and stop collecting.
+    pub fn reset(&mut self) {
+        *self = Calibration::new();
+    }
+
+    /// Feed a raw sample in µT. Only has an effect while collecting.
+    pub fn update(&mut self, xyz: (f32, f32, f32)) {
+        if self.state != CalibrationState::Collecting {
+            return;
+        }
+
+        let s = [xyz.0, xyz.1, xyz.2];
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(s[i]);
+            self.max[i] = self.max[i].max(s[i]);
+            // samples already lack the device offset
+            self.hard_iron[i] = self.device_offset[i] + (self.max[i] + self.min[i]) / 2.0;
+        }
+    }
+
+    /// Apply hard- then soft-iron correction to a sample in µT. Only the
+    /// hard-iron offset not already removed by the device is subtracted.
+    pub fn apply(&self, xyz: (f32, f32, f32)) -> (f32, f32, f32) {
+        let v = [
+            xyz.0 - (self.hard_iron[0] - self.device_offset[0]),
+            xyz.1 - (self.hard_iron[1] - self.device_offset[1]),
+            xyz.2 - (self.hard_iron[2] - self.device_offset[2]),
+        ];
+        match &self.soft_iron {
+            Some(m) => (
+                m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+                m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+                m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+            ),
+            None => (v[0], v[1], v[2]),
+        }
+    }
+}
+
+// Stored calibration layout, all fields little-endian:
+//   0  magic "LM"
+//   2  format version
+//   3  flags (bit 0: soft-iron present, bit 1: temperature present)
+//   4  hard-iron offsets, 3 × f32
+//  16  soft-iron matrix row-major, 9 × f32 (zero if absent)
+//  52  temperature, f32 (zero if absent)
+//  56  sequence, u32
+//  60  CRC-32 (IEEE) of bytes 0..60
+const MAGIC: [u8; 2] = *b"LM";
+const FORMAT_VERSION: u8 = 1;
+const FLAG_SOFT_IRON: u8 = 1 << 0;
+const FLAG_TEMPERATURE: u8 = 1 << 1;
+
+/// Size of the [`Calibration::to_bytes`] encoding.
+pub const CALIBRATION_BYTES: usize = 64;
+
+/// Why [`Calibration::from_bytes`] rejected its input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DecodeError {
+    /// Not `CALIBRATION_BYTES` long
+    Length,
+    /// Missing magic, not a stored calibration
+    Magic,
+    /// Written by an unknown format version
+    Version(u8),
+    /// CRC mismatch, the data is corrupt
+    Checksum,
+}
+
+impl Calibration {
+    /// Encode into the stable, versioned and checksummed storage format.
+    /// The collecting state is not stored.
+    pub fn to_bytes(&self) -> [u8; CALIBRATION_BYTES] {
+        let mut bytes = [0u8; CALIBRATION_BYTES];
+        bytes[0..2].copy_from_slice(&MAGIC);
+        bytes[2] = FORMAT_VERSION;
+
+        let mut flags = 0;
+        if self.soft_iron.is_some() {
+            flags |= FLAG_SOFT_IRON;
+        }
+        if self.temperature.is_some() {
+            flags |= FLAG_TEMPERATURE;
+        }
+        bytes[3] = flags;
+
+        let soft_iron = self.soft_iron.unwrap_or_default();
+        let temperature = self.temperature.unwrap_or_default();
+        let floats = self
+            .hard_iron
+            .iter()
+            .chain(soft_iron.iter().flatten())
+            .chain(core::iter::once(&temperature));
+        for (chunk, value) in bytes[4..56].chunks_exact_mut(4).zip(floats) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[56..60].copy_from_slice(&self.sequence.to_le_bytes());
+
+        let crc = crc32(&bytes[..60]);
+        bytes[60..64].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    /// Decode a calibration written by `to_bytes`. The result is frozen.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() != CALIBRATION_BYTES {
+            return Err(DecodeError::Length);
+        }
+        if bytes[0..2] != MAGIC {
+            return Err(DecodeError::Magic);
+        }
+        if bytes[2] != FORMAT_VERSION {
+            return Err(DecodeError::Version(bytes[2]));
+        }
+        if crc32(&bytes[..60]).to_le_bytes() != bytes[60..64] {
+            return Err(DecodeError::Checksum);
+        }
+
+        let flags = bytes[3];
+        let mut floats = [0.0f32; 13];
+        for (value, chunk) in floats.iter_mut().zip(bytes[4..56].chunks_exact(4)) {
+            *value = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+
+        let mut calibration = Calibration::new()
+            .with_hard_iron([floats[0], floats[1], floats[2]])
+            .with_sequence(u32::from_le_bytes([
+                bytes[56], bytes[57], bytes[58], bytes[59],
+            ]));
+        if flags & FLAG_SOFT_IRON != 0 {
+            calibration = calibration.with_soft_iron([
+                [floats[3], floats[4], floats[5]],
+                [floats[6], floats[7], floats[8]],
+                [floats[9], floats[10], floats[11]],
+            ]);
+        }
+        if flags & FLAG_TEMPERATURE != 0 {
+            calibration = calibration.with_temperature(floats[12]);
+        }
+
+        Ok(calibration)
+    }
+}
+
+// CRC-32/ISO-HDLC, bitwise to avoid a lookup table
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+impl Default for EllipsoidFit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EllipsoidFit {
+    pub const fn new() -> Self {
+        EllipsoidFit {
+            ata: [[0.0; PARAMS]; PARAMS],
+            atb: [0.0; PARAMS],
+            count: 0,
+            min: [f32::MAX; 3],
+            max: [f32::MIN; 3],
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn add_sample(&mut self, xyz: (f32, f32, f32)) {
+        let s = [xyz.0, xyz.1, xyz.2];
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(s[i]);
+            self.max[i] = self.max[i].max(s[i]);
+        }
+
+        let (x, y, z) = (
+            xyz.0 as f64 * SCALE,
+            xyz.1 as f64 * SCALE,
+            xyz.2 as f64 * SCALE,
+        );
+        let row = [
+            x * x,
+            y * y,
+            z * z,
+            2.0 * x * y,
+            2.0 * x * z,
+            2.0 * y * z,
+            2.0 * x,
+            2.0 * y,
+            2.0 * z,
+        ];
+        for i in 0..PARAMS {
+            for j in 0..PARAMS {
+                self.ata[i][j] += row[i] * row[j];
+            }
+            self.atb[i] += row[i];
+        }
+        self.count += 1;
+    }
+
+    /// Fit the collected samples. Returns `None` when there are too few
+    /// samples, they are degenerate (e.g. all in one plane), or the quadric
+    /// is not an ellipsoid.
+    pub fn fit(&self) -> Option<EllipsoidCalibration> {
+        if (self.count as usize) < PARAMS {
+            return None;
+        }
+
+        let p = solve(self.ata, self.atb)?;
+        let m = [[p[0], p[3], p[4]], [p[3], p[1], p[5]], [p[4], p[5], p[2]]];
+        let u = [p[6], p[7], p[8]];
+
+        // center = -M⁻¹ u
+        let m_inv = invert3(&m)?;
+        let center = mul3(&m_inv, &u).map(|c| -c);
+
+        // (v - center)ᵀ M (v - center) = k
+        let k = 1.0 + dot3(&center, &mul3(&m, &center));
+        if k <= 0.0 {
+            return None;
+        }
+        let m = m.map(|row| row.map(|v| v / k));
+
+        let (eigenvalues, vectors) = jacobi_eigen(m);
+        if eigenvalues.iter().any(|&l| l <= 0.0) {
+            return None;
+        }
+
+        // radius of the sphere with the same volume as the ellipsoid
+        let radius = 1.0 / cbrt(sqrt(eigenvalues[0] * eigenvalues[1] * eigenvalues[2]));
+
+        // W = V · diag(√λ · radius) · Vᵀ
+        let mut soft_iron = [[0.0f32; 3]; 3];
+        for (i, row) in soft_iron.iter_mut().enumerate() {
+            for (j, w) in row.iter_mut().enumerate() {
+                let mut sum = 0.0;
+                for l in 0..3 {
+                    sum += vectors[i][l] * sqrt(eigenvalues[l]) * radius * vectors[j][l];
+                }
+                *w = sum as f32;
+            }
+        }
+
+        // algebraic residual from the normal equations: |Dp - 1|²
+        let mut rss = self.count as f64;
+        for i in 0..PARAMS {
+            rss -= 2.0 * p[i] * self.atb[i];
+            for j in 0..PARAMS {
+                rss += p[i] * self.ata[i][j] * p[j];
+            }
+        }
+        // Dp - 1 ≈ 2·k·(relative radial error) near the surface
+        let residual = sqrt(rss.max(0.0) / self.count as f64) / (2.0 * k);
+
+        // ellipsoid half-extent along each axis is √((M/k)⁻¹)ᵢᵢ
+        let m_inv = invert3(&m)?;
+        let mut coverage = 1.0f64;
+        for i in 0..3 {
+            let extent = sqrt(m_inv[i][i]) / SCALE;
+            let span = (self.max[i] - self.min[i]) as f64;
+            coverage = coverage.min(span / (2.0 * extent));
+        }
+
+        Some(EllipsoidCalibration {
+            hard_iron: center.map(|c| (c / SCALE) as f32),
+            soft_iron,
+            field_strength: (radius / SCALE) as f32,
+            residual: residual as f32,
+            coverage: coverage.clamp(0.0, 1.0) as f32,
+        })
+    }
+}
+
+impl<IFACE, DELAY, E> Lis2mdl<IFACE, DELAY>
+where
+    DELAY: DelayNs,
+    IFACE: Interface<Error = E>,
+{
+    /// `read` a sample and add it to an ellipsoid fit.
+    pub fn read_into_fit(&mut self, fit: &mut EllipsoidFit) -> Result<(), Error<E>> {
+        self.read()?;
+        fit.add_sample(self.current_xyz());
+
+        Ok(())
+    }
+
+    pub fn calibration(&self) -> &Calibration {
+        &self.calibration
+    }
+
+    /// Replace the calibration, e.g. with one loaded by `from_bytes`. The
+    /// device offset currently in the OFFSET registers carries over; call
+    /// `store_hard_iron_offset` to write the new one.
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        let device_offset = self.calibration.device_offset;
+        self.calibration = calibration;
+        self.calibration.device_offset = device_offset;
+    }
+
+    /// Learn hard-iron offsets from every subsequent `read` until
+    /// `finish_calibration`.
+    pub fn begin_calibration(&mut self) {
+        self.calibration.begin();
+    }
+
+    pub fn finish_calibration(&mut self) {
+        self.calibration.finish();
+    }
+
+    pub fn reset_calibration(&mut self) {
+        self.calibration.reset();
+    }
+
+    /// The last sample in µT with the calibration applied.
+    pub fn calibrated_xyz(&mut self) -> (f32, f32, f32) {
+        let xyz = self.current_xyz();
+        self.calibration.apply(xyz)
+    }
+}
+
+// Gaussian elimination with partial pivoting
+fn solve(mut a: [[f64; PARAMS]; PARAMS], mut b: [f64; PARAMS]) -> Option<[f64; PARAMS]> {
+    for col in 0..PARAMS {
+        let pivot = (col..PARAMS).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        for row in col + 1..PARAMS {
+            let factor = a[row][col] / a[col][col];
+            for k in col..PARAMS {
+                a[row][k] -= factor * a[col][k];
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = [0.0; PARAMS];
+    for row in (0..PARAMS).rev() {
+        let mut sum = b[row];
+        for k in row + 1..PARAMS {
+            sum -= a[row][k] * x[k];
+        }
+        x[row] = sum / a[row][row];
+    }
+
+    Some(x)
+}
+
+fn mul3(m: &[[f64; 3]; 3], v: &[f64; 3]) -> [f64; 3] {
+    [dot3(&m[0], v), dot3(&m[1], v), dot3(&m[2], v)]
+}
+
+fn dot3(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn invert3(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let adj = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+    let det = m[0][0] * adj[0][0] + m[0][1] * adj[1][0] + m[0][2] * adj[2][0];
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    Some(adj.map(|row| row.map(|v| v / det)))
+}
+
+// Cyclic Jacobi eigen-decomposition of a symmetric 3x3 matrix. Returns the
+// eigenvalues and the eigenvectors as matrix columns.
+fn jacobi_eigen(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    for _ in 0..32 {
+        let off = a[0][1] * a[0][1] + a[0][2] * a[0][2] + a[1][2] * a[1][2];
+        if off < 1e-24 {
+            break;
+        }
+
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q].abs() < 1e-30 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + sqrt(theta * theta + 1.0));
+            let c = 1.0 / sqrt(t * t + 1.0);
+            let s = t * c;
+
+            for k in 0..3 {
+                let (akp, akq) = (a[k][p], a[k][q]);
+                a[k][p] = c * akp - s * akq;
+                a[k][q] = s * akp + c * akq;
+            }
+            for k in 0..3 {
+                let (apk, aqk) = (a[p][k], a[q][k]);
+                a[p][k] = c * apk - s * aqk;
+                a[q][k] = s * apk + c * aqk;
+            }
+            for row in v.iter_mut() {
+                let (vp, vq) = (row[p], row[q]);
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+
+    ([a[0][0], a[1][1], a[2][2]], v)
+}
+
+// Newton's method from an exponent-halving first guess
+pub(crate) fn sqrt(x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let mut r = f64::from_bits((x.to_bits() >> 1) + 0x1FF8_0000_0000_0000);
+    for _ in 0..6 {
+        r = 0.5 * (r + x / r);
+    }
+    r
+}
+
+fn cbrt(x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let mut r = f64::from_bits(x.to_bits() / 3 + 0x2A9F_7893_782D_A1CE);
+    for _ in 0..8 {
+        r = (2.0 * r + x / (r * r)) / 3.0;
+    }
+    r
+}
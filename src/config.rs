@@ -0,0 +1,160 @@
+use crate::register::{CfgRegA, CfgRegB, Mode, OutputDataRate};
+
+/// Resolution/power trade-off, `LP` in `CFG_REG_A`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PowerMode {
+    #[default]
+    HighResolution,
+    LowPower,
+}
+
+/// How often the set pulse re-magnetizes the sensing elements, `Set_FREQ`
+/// in `CFG_REG_B`.
+///
+/// Periodic pulses keep the sensor recovered from strong external fields at
+/// the cost of some current; with pulses only at power-on, a field that
+/// saturates the sensor can leave an offset until the next power cycle.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SetPulse {
+    /// Every 63 ODR periods (device default)
+    #[default]
+    Every63Samples,
+    /// Only after power-down (leaving idle mode)
+    PowerOnOnly,
+}
+
+/// Measurement configuration held in `CFG_REG_A` and `CFG_REG_B`.
+///
+/// The default matches the register values written by `Lis2mdl::start`:
+/// 10 Hz continuous high-resolution mode with every option disabled. ST
+/// recommends enabling temperature compensation for correct operation.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Config {
+    pub odr: OutputDataRate,
+    pub mode: Mode,
+    pub power_mode: PowerMode,
+    pub temperature_compensation: bool,
+    pub low_pass_filter: bool,
+    pub offset_cancellation: bool,
+    pub offset_cancellation_one_shot: bool,
+    pub set_pulse: SetPulse,
+    pub interrupt_on_corrected_data: bool,
+}
+
+impl Config {
+    pub const fn new() -> Self {
+        Config {
+            odr: OutputDataRate::Hz10,
+            mode: Mode::Continuous,
+            power_mode: PowerMode::HighResolution,
+            temperature_compensation: false,
+            low_pass_filter: false,
+            offset_cancellation: false,
+            offset_cancellation_one_shot: false,
+            set_pulse: SetPulse::Every63Samples,
+            interrupt_on_corrected_data: false,
+        }
+    }
+
+    pub const fn with_odr(mut self, odr: OutputDataRate) -> Self {
+        self.odr = odr;
+        self
+    }
+
+    pub const fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub const fn with_power_mode(mut self, power_mode: PowerMode) -> Self {
+        self.power_mode = power_mode;
+        self
+    }
+
+    pub const fn with_temperature_compensation(mut self, enabled: bool) -> Self {
+        self.temperature_compensation = enabled;
+        self
+    }
+
+    /// Low-pass filter, bandwidth ODR/4 instead of ODR/2. Lowers noise but
+    /// adds a sample of lag to field changes.
+    pub const fn with_low_pass_filter(mut self, enabled: bool) -> Self {
+        self.low_pass_filter = enabled;
+        self
+    }
+
+    /// Offset cancellation: alternate set and reset pulses and average the
+    /// pairs, removing the sensor's own offset and its temperature drift.
+    /// Costs extra current and halves the effective noise averaging per
+    /// output sample; ST recommends it for continuous mode.
+    pub const fn with_offset_cancellation(mut self, enabled: bool) -> Self {
+        self.offset_cancellation = enabled;
+        self
+    }
+
+    /// Offset cancellation in single-shot mode. Needs `offset_cancellation`
+    /// as well, and takes effect only from the second single measurement,
+    /// since the first has no opposite pulse to pair with.
+    pub const fn with_offset_cancellation_one_shot(mut self, enabled: bool) -> Self {
+        self.offset_cancellation_one_shot = enabled;
+        self
+    }
+
+    pub const fn with_set_pulse(mut self, set_pulse: SetPulse) -> Self {
+        self.set_pulse = set_pulse;
+        self
+    }
+
+    /// Check the threshold interrupt against data with the OFFSET registers
+    /// subtracted (INT_on_DataOFF) instead of the uncorrected data. Enable
+    /// when hard-iron offsets are stored on the device, or thresholds see
+    /// the hard-iron field.
+    pub const fn with_interrupt_on_corrected_data(mut self, enabled: bool) -> Self {
+        self.interrupt_on_corrected_data = enabled;
+        self
+    }
+
+    /// Time between samples at the configured ODR, in µs.
+    pub const fn sample_period_us(&self) -> u32 {
+        1_000_000 / self.odr.hz()
+    }
+
+    pub const fn cfg_reg_a(&self) -> CfgRegA {
+        CfgRegA::from_bits(0)
+            .with_comp_temp_en(self.temperature_compensation)
+            .with_lp(matches!(self.power_mode, PowerMode::LowPower))
+            .with_odr(self.odr)
+            .with_mode(self.mode)
+    }
+
+    pub const fn cfg_reg_b(&self) -> CfgRegB {
+        CfgRegB::from_bits(0)
+            .with_lpf(self.low_pass_filter)
+            .with_off_canc(self.offset_cancellation)
+            .with_set_freq(matches!(self.set_pulse, SetPulse::PowerOnOnly))
+            .with_int_on_data_off(self.interrupt_on_corrected_data)
+            .with_off_canc_one_shot(self.offset_cancellation_one_shot)
+    }
+
+    pub const fn from_registers(a: CfgRegA, b: CfgRegB) -> Self {
+        Config {
+            odr: a.odr(),
+            mode: a.mode(),
+            power_mode: if a.lp() {
+                PowerMode::LowPower
+            } else {
+                PowerMode::HighResolution
+            },
+            temperature_compensation: a.comp_temp_en(),
+            low_pass_filter: b.lpf(),
+            offset_cancellation: b.off_canc(),
+            offset_cancellation_one_shot: b.off_canc_one_shot(),
+            set_pulse: if b.set_freq() {
+                SetPulse::PowerOnOnly
+            } else {
+                SetPulse::Every63Samples
+            },
+            interrupt_on_corrected_data: b.int_on_data_off(),
+        }
+    }
+}
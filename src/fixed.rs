@@ -0,0 +1,104 @@
+// Integer conversions and heading for targets without an FPU
+
+use embedded_hal::delay::DelayNs;
+
+use crate::register::Register;
+use crate::{Error, Interface, Lis2mdl};
+
+/// Output scale: 1.5 mG = 150 nT per LSB, exactly.
+pub const NANOTESLA_PER_LSB: i32 = 150;
+
+// atan(2^-i) in units of 0.0001°
+const ATAN_TABLE: [i32; 17] = [
+    450000, 265651, 140362, 71250, 35763, 17899, 8952, 4476, 2238, 1119, 560, 280, 140, 70, 35, 17,
+    9,
+];
+
+pub const fn raw_to_nanotesla(raw: i16) -> i32 {
+    raw as i32 * NANOTESLA_PER_LSB
+}
+
+/// Die temperature in m°C: 8 LSB/°C, 25 °C at zero.
+pub const fn raw_to_millicelsius(raw: i16) -> i32 {
+    raw as i32 * 125 + 25_000
+}
+
+/// Heading in centidegrees [0, 36000) of a horizontal field vector, measured
+/// from the X axis towards the Y axis, like `heading_from_xy`. Uses CORDIC
+/// with shifts and adds only; error is within ±1 centidegree.
+pub fn heading_centidegrees(x: i32, y: i32) -> u16 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+
+    // The CORDIC gain (~1.65) times √2 must fit in an i32, so bring the
+    // larger component to [2^28, 2^29) for both headroom and resolution
+    let (mut x, mut y) = (x as i64, y as i64);
+    let largest = x.abs().max(y.abs());
+    let shift = largest.leading_zeros() as i32 - (64 - 29);
+    if shift >= 0 {
+        x <<= shift;
+        y <<= shift;
+    } else {
+        x >>= -shift;
+        y >>= -shift;
+    }
+    let (mut x, mut y) = (x as i32, y as i32);
+
+    // rotate into the right half-plane
+    let mut angle = 0;
+    if x < 0 {
+        (x, y) = (-x, -y);
+        angle = 1_800_000;
+    }
+
+    for (i, step) in ATAN_TABLE.iter().enumerate() {
+        let (dx, dy) = (y >> i, x >> i);
+        if y > 0 {
+            x += dx;
+            y -= dy;
+            angle += step;
+        } else {
+            x -= dx;
+            y += dy;
+            angle -= step;
+        }
+    }
+
+    let centidegrees = (angle + 50).div_euclid(100).rem_euclid(36_000);
+    centidegrees as u16
+}
+
+impl<IFACE, DELAY, E> Lis2mdl<IFACE, DELAY>
+where
+    DELAY: DelayNs,
+    IFACE: Interface<Error = E>,
+{
+    /// The last sample in nT, in the device frame. Divide by 100 for mG.
+    pub fn current_xyz_nanotesla(&self) -> (i32, i32, i32) {
+        let (x, y, z) = self
+            .orientation
+            .apply_raw((self.mag_x, self.mag_y, self.mag_z));
+
+        (
+            raw_to_nanotesla(x),
+            raw_to_nanotesla(y),
+            raw_to_nanotesla(z),
+        )
+    }
+
+    /// Heading of the last sample in centidegrees. No software calibration
+    /// is applied; use the OFFSET registers for hard-iron correction.
+    pub fn heading_centidegrees(&self) -> u16 {
+        let (x, y, _) = self
+            .orientation
+            .apply_raw((self.mag_x, self.mag_y, self.mag_z));
+
+        heading_centidegrees(x as i32, y as i32)
+    }
+
+    /// Die temperature in m°C.
+    pub fn read_temperature_millicelsius(&mut self) -> Result<i32, Error<E>> {
+        self.read_reg16(Register::TempOutL).map(raw_to_millicelsius)
+    }
+}
@@ -0,0 +1,91 @@
+use embedded_hal::delay::DelayNs;
+#[allow(unused_imports)] // float methods resolve to std when testing
+use micromath::F32Ext;
+
+use crate::{Interface, Lis2mdl};
+
+/// Heading in degrees [0, 360) of a horizontal field vector, measured from
+/// the X axis towards the Y axis.
+pub fn heading_from_xy(x: f32, y: f32) -> f32 {
+    let heading = y.atan2(x).to_degrees();
+    if heading < 0.0 {
+        heading + 360.0
+    } else {
+        heading
+    }
+}
+
+/// Add a declination (degrees, east positive) to a magnetic heading.
+pub fn magnetic_to_true(heading: f32, declination: f32) -> f32 {
+    let heading = (heading + declination) % 360.0;
+    if heading < 0.0 {
+        heading + 360.0
+    } else {
+        heading
+    }
+}
+
+/// Pitch and roll in degrees from an accelerometer reading in the
+/// magnetometer's axes, +1 g on Z when level and face up. Any unit works.
+pub fn pitch_roll(accel: (f32, f32, f32)) -> (f32, f32) {
+    let (ax, ay, az) = accel;
+    let roll = ay.atan2(az);
+    let pitch = (-ax).atan2(ay * roll.sin() + az * roll.cos());
+
+    (pitch.to_degrees(), roll.to_degrees())
+}
+
+/// Project a field vector onto the horizontal plane given pitch and roll in
+/// degrees, returning the horizontal X and Y components.
+pub fn tilt_compensate(mag: (f32, f32, f32), pitch: f32, roll: f32) -> (f32, f32) {
+    let (mx, my, mz) = mag;
+    let (sin_p, cos_p) = (pitch.to_radians().sin(), pitch.to_radians().cos());
+    let (sin_r, cos_r) = (roll.to_radians().sin(), roll.to_radians().cos());
+
+    let xh = mx * cos_p + my * sin_p * sin_r + mz * sin_p * cos_r;
+    let yh = my * cos_r - mz * sin_r;
+
+    (xh, yh)
+}
+
+impl<IFACE, DELAY, E> Lis2mdl<IFACE, DELAY>
+where
+    DELAY: DelayNs,
+    IFACE: Interface<Error = E>,
+{
+    /// Heading in degrees using all three axes of the last calibrated
+    /// sample, levelled with an accelerometer reading taken in the same
+    /// axes (+1 g on Z when level). Matches `get_heading` when level.
+    pub fn tilt_compensated_heading(&mut self, accel: (f32, f32, f32)) -> f32 {
+        let (pitch, roll) = pitch_roll(accel);
+        self.tilt_compensated_heading_from_angles(pitch, roll)
+    }
+
+    /// Like `tilt_compensated_heading`, with pitch and roll in degrees.
+    pub fn tilt_compensated_heading_from_angles(&mut self, pitch: f32, roll: f32) -> f32 {
+        let (xh, yh) = tilt_compensate(self.calibrated_xyz(), pitch, roll);
+        heading_from_xy(xh, yh)
+    }
+
+    /// Declination in degrees, positive when magnetic north is east of true
+    /// north. Used by `true_heading`.
+    pub fn set_declination(&mut self, declination: f32) {
+        self.declination = declination;
+    }
+
+    pub fn declination(&self) -> f32 {
+        self.declination
+    }
+
+    /// Set the declination from the built-in World Magnetic Model for a
+    /// location in degrees and a decimal year, e.g. 2026.5.
+    #[cfg(feature = "wmm")]
+    pub fn set_declination_from_location(&mut self, latitude: f32, longitude: f32, year: f32) {
+        self.declination = crate::wmm::WMM2025.declination(latitude, longitude, year);
+    }
+
+    /// `get_heading` corrected for declination, relative to true north.
+    pub fn true_heading(&mut self) -> f32 {
+        magnetic_to_true(self.get_heading(), self.declination)
+    }
+}
@@ -0,0 +1,149 @@
+use embedded_hal::i2c::{I2c, Operation as I2cOperation};
+use embedded_hal::spi::{Operation as SpiOperation, SpiDevice};
+
+use crate::Error;
+use crate::register::CfgRegC;
+
+// SPI address byte: bit 7 selects read (1) or write (0)
+pub(crate) const SPI_READ: u8 = 0x80;
+
+// Framing shared by the blocking and async transports
+
+// `CFG_REG_C` bits that select I²C
+pub(crate) const I2C_CFG_REG_C: CfgRegC = CfgRegC::from_bits(0);
+
+// First byte of an SPI access to `reg`
+pub(crate) const fn spi_address(reg: u8, read: bool) -> u8 {
+    if read {
+        reg | SPI_READ
+    } else {
+        reg & !SPI_READ
+    }
+}
+
+/// Bus transport used by the driver.
+///
+/// The LIS2MDL auto-increments the register address on multi-byte accesses
+/// over both I²C and SPI, so bursts only send the first address.
+pub trait Interface {
+    type Error;
+
+    fn write_registers(&mut self, reg: u8, data: &[u8]) -> Result<(), Error<Self::Error>>;
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Error<Self::Error>>;
+
+    /// `CFG_REG_C` bits that select this interface on the device.
+    fn cfg_reg_c(&self) -> CfgRegC;
+}
+
+/// I²C transport.
+#[derive(Debug)]
+pub struct I2cInterface<I2C> {
+    pub(crate) i2c: I2C,
+    pub(crate) address: u8,
+}
+
+impl<I2C> I2cInterface<I2C> {
+    pub fn new<A: Into<crate::Address>>(i2c: I2C, address: A) -> Self {
+        I2cInterface {
+            i2c,
+            address: address.into().0,
+        }
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+impl<I2C, E> Interface for I2cInterface<I2C>
+where
+    I2C: I2c<Error = E>,
+{
+    type Error = E;
+
+    fn write_registers(&mut self, reg: u8, data: &[u8]) -> Result<(), Error<E>> {
+        // adjacent writes go out without a repeated start
+        let mut operations = [I2cOperation::Write(&[reg]), I2cOperation::Write(data)];
+        self.i2c
+            .transaction(self.address, &mut operations)
+            .map_err(Error::I2C)
+    }
+
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        let mut operations = [I2cOperation::Write(&[reg]), I2cOperation::Read(buffer)];
+        self.i2c
+            .transaction(self.address, &mut operations)
+            .map_err(Error::I2C)
+    }
+
+    fn cfg_reg_c(&self) -> CfgRegC {
+        I2C_CFG_REG_C
+    }
+}
+
+/// SPI wiring. The LIS2MDL powers up in 3-wire mode; 4-wire mode is
+/// enabled by `start` through the 4WSPI bit.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SpiWires {
+    /// SDI/SDO shared on the SDA pin
+    #[default]
+    Three,
+    /// SDO on its own pin
+    Four,
+}
+
+impl SpiWires {
+    // `CFG_REG_C` bits that select SPI with this wiring
+    pub(crate) const fn cfg_reg_c(self) -> CfgRegC {
+        CfgRegC::from_bits(0)
+            .with_i2c_dis(true)
+            .with_four_wire_spi(matches!(self, SpiWires::Four))
+    }
+}
+
+/// SPI transport.
+///
+/// In 3-wire mode the `SpiDevice` implementation is responsible for turning
+/// the data line around between the address byte and the read phase.
+#[derive(Debug)]
+pub struct SpiInterface<SPI> {
+    pub(crate) spi: SPI,
+    pub(crate) wires: SpiWires,
+}
+
+impl<SPI> SpiInterface<SPI> {
+    pub fn new(spi: SPI, wires: SpiWires) -> Self {
+        SpiInterface { spi, wires }
+    }
+
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+}
+
+impl<SPI, E> Interface for SpiInterface<SPI>
+where
+    SPI: SpiDevice<Error = E>,
+{
+    type Error = E;
+
+    fn write_registers(&mut self, reg: u8, data: &[u8]) -> Result<(), Error<E>> {
+        let mut operations = [
+            SpiOperation::Write(&[spi_address(reg, false)]),
+            SpiOperation::Write(data),
+        ];
+        self.spi.transaction(&mut operations).map_err(Error::Spi)
+    }
+
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        let mut operations = [
+            SpiOperation::Write(&[spi_address(reg, true)]),
+            SpiOperation::Read(buffer),
+        ];
+        self.spi.transaction(&mut operations).map_err(Error::Spi)
+    }
+
+    fn cfg_reg_c(&self) -> CfgRegC {
+        self.wires.cfg_reg_c()
+    }
+}
@@ -0,0 +1,145 @@
+use embedded_hal::delay::DelayNs;
+
+use crate::fixed::NANOTESLA_PER_LSB;
+use crate::register::{CfgRegC, IntCtrlReg, IntSourceReg, Register};
+use crate::{Error, Interface, Lis2mdl};
+#[cfg(feature = "float")]
+use crate::{LIS2MDL_MAG_LSB, LIS2MDL_MILLIGAUSS_TO_MICROTESLA};
+
+/// Threshold interrupt settings held in `INT_CTRL_REG`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct InterruptConfig {
+    pub x: bool,
+    pub y: bool,
+    pub z: bool,
+    /// INT pin and INT bit are active high instead of active low (IEA)
+    pub active_high: bool,
+    /// Hold the interrupt until INT_SOURCE_REG is read instead of pulsing it (IEL)
+    pub latched: bool,
+}
+
+impl InterruptConfig {
+    pub const fn new() -> Self {
+        InterruptConfig {
+            x: false,
+            y: false,
+            z: false,
+            active_high: false,
+            latched: false,
+        }
+    }
+
+    pub const fn with_axes(mut self, x: bool, y: bool, z: bool) -> Self {
+        self.x = x;
+        self.y = y;
+        self.z = z;
+        self
+    }
+
+    pub const fn with_active_high(mut self, active_high: bool) -> Self {
+        self.active_high = active_high;
+        self
+    }
+
+    pub const fn with_latched(mut self, latched: bool) -> Self {
+        self.latched = latched;
+        self
+    }
+
+    /// The interrupt engine is enabled (IEN) whenever an axis is.
+    pub const fn int_ctrl_reg(&self) -> IntCtrlReg {
+        IntCtrlReg::from_bits(0)
+            .with_xien(self.x)
+            .with_yien(self.y)
+            .with_zien(self.z)
+            .with_iea(self.active_high)
+            .with_iel(self.latched)
+            .with_ien(self.x || self.y || self.z)
+    }
+
+    pub const fn from_register(reg: IntCtrlReg) -> Self {
+        InterruptConfig {
+            x: reg.xien(),
+            y: reg.yien(),
+            z: reg.zien(),
+            active_high: reg.iea(),
+            latched: reg.iel(),
+        }
+    }
+}
+
+/// Decoded `INT_SOURCE_REG`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct InterruptSource {
+    /// X, Y, Z exceeded the threshold in the positive direction
+    pub positive: [bool; 3],
+    /// X, Y, Z exceeded the threshold in the negative direction
+    pub negative: [bool; 3],
+    /// Internal measurement range overflow (MROI)
+    pub overflow: bool,
+    /// An interrupt event occurred (INT)
+    pub triggered: bool,
+}
+
+impl From<IntSourceReg> for InterruptSource {
+    fn from(reg: IntSourceReg) -> Self {
+        InterruptSource {
+            positive: [reg.p_th_s_x(), reg.p_th_s_y(), reg.p_th_s_z()],
+            negative: [reg.n_th_s_x(), reg.n_th_s_y(), reg.n_th_s_z()],
+            overflow: reg.mroi(),
+            triggered: reg.int(),
+        }
+    }
+}
+
+impl<IFACE, DELAY, E> Lis2mdl<IFACE, DELAY>
+where
+    DELAY: DelayNs,
+    IFACE: Interface<Error = E>,
+{
+    /// Set the threshold in LSB (1.5 mG). The threshold is unsigned and
+    /// applies symmetrically to both directions; values above 15 bits are
+    /// clamped.
+    pub fn set_interrupt_threshold_raw(&mut self, threshold: u16) -> Result<(), Error<E>> {
+        let threshold = threshold.min(i16::MAX as u16);
+        self.write_reg16(Register::IntThsL, threshold as i16)
+    }
+
+    #[cfg(feature = "float")]
+    /// Set the threshold in µT.
+    pub fn set_interrupt_threshold(&mut self, microtesla: f32) -> Result<(), Error<E>> {
+        let lsb = microtesla / (LIS2MDL_MAG_LSB * LIS2MDL_MILLIGAUSS_TO_MICROTESLA);
+        // float to int casts saturate, negative values become 0
+        self.set_interrupt_threshold_raw((lsb + 0.5) as u16)
+    }
+
+    /// Set the threshold in nT, rounded to the nearest LSB.
+    pub fn set_interrupt_threshold_nanotesla(&mut self, nanotesla: u32) -> Result<(), Error<E>> {
+        let lsb = nanotesla.saturating_add(NANOTESLA_PER_LSB as u32 / 2) / NANOTESLA_PER_LSB as u32;
+        self.set_interrupt_threshold_raw(lsb.min(u16::MAX as u32) as u16)
+    }
+
+    pub fn interrupt_threshold_raw(&mut self) -> Result<u16, Error<E>> {
+        self.read_reg16(Register::IntThsL).map(|ths| ths as u16)
+    }
+
+    pub fn configure_interrupt(&mut self, config: InterruptConfig) -> Result<(), Error<E>> {
+        self.write_reg(config.int_ctrl_reg())
+    }
+
+    pub fn interrupt_config(&mut self) -> Result<InterruptConfig, Error<E>> {
+        self.read_reg::<IntCtrlReg>()
+            .map(InterruptConfig::from_register)
+    }
+
+    /// Route the threshold interrupt to the INT/DRDY pin (INT_on_PIN).
+    pub fn route_interrupt_to_pin(&mut self, enabled: bool) -> Result<(), Error<E>> {
+        self.modify_reg::<CfgRegC, _>(|reg| reg.with_int_on_pin(enabled))
+            .map(|_| ())
+    }
+
+    /// Read INT_SOURCE_REG. This clears a latched interrupt.
+    pub fn interrupt_source(&mut self) -> Result<InterruptSource, Error<E>> {
+        self.read_reg::<IntSourceReg>().map(InterruptSource::from)
+    }
+}
@@ -1,161 +1,471 @@
 #![no_std]
 use embedded_hal::delay::DelayNs;
-use embedded_hal::i2c::Operation;
-use micromath::F32Ext;
+
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "float")]
+pub mod calibration;
+pub mod config;
+pub mod fixed;
+#[cfg(feature = "float")]
+pub mod heading;
+pub mod interface;
+pub mod interrupt;
+pub mod measurement;
+mod offset;
+pub mod orientation;
+pub mod pin;
+pub mod register;
+pub mod self_test;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+#[cfg(feature = "float")]
+pub mod units;
+#[cfg(feature = "wmm")]
+pub mod wmm;
+
+#[cfg(feature = "async")]
+pub use asynch::{AsyncInterface, Lis2mdlAsync};
+#[cfg(feature = "float")]
+pub use calibration::{
+    CALIBRATION_BYTES, Calibration, CalibrationState, DecodeError, EllipsoidCalibration,
+    EllipsoidFit,
+};
+pub use config::{Config, PowerMode, SetPulse};
+pub use interface::{I2cInterface, Interface, SpiInterface, SpiWires};
+pub use interrupt::{InterruptConfig, InterruptSource};
+pub use measurement::Measurement;
+pub use orientation::{Direction, Orientation};
+pub use pin::{NoPin, PinFunction, WithPin};
+
+pub use self_test::SelfTestReport;
+#[cfg(feature = "float")]
+pub use units::{Cardinal, Heading, MagneticField};
+
+pub use register::{
+    CHIP_ID, CfgRegA, CfgRegB, CfgRegC, IntCtrlReg, IntSourceReg, Mode, OutputDataRate, Register,
+    RegisterBits, StatusReg, WritableRegister,
+};
 
 const DEFAULT_DEVICE_ID: u8 = 0x1E;
-pub const LIS2MDL_CFG_REG_A: u8 = 0x60;
-pub const LIS2MDL_CFG_REG_B: u8 = 0x61;
-pub const LIS2MDL_CFG_REG_C: u8 = 0x62;
-const LIS2MDL_OUTX_L_REG: u8 = 0x68;
-const LIS2MDL_WHO_AM_I_REG: u8 = 0x4F;
-const DELAY_TIME: u32 = 125;
-const CHIP_ID: u8 = 0x40;
+pub const LIS2MDL_CFG_REG_A: u8 = Register::CfgRegA.addr();
+pub const LIS2MDL_CFG_REG_B: u8 = Register::CfgRegB.addr();
+pub const LIS2MDL_CFG_REG_C: u8 = Register::CfgRegC.addr();
+const DELAY_TIME: u32 = 125; // µs between STATUS_REG polls
+// Sample wait when averaging at 100 Hz, allowing several output periods
+const AVERAGE_TIMEOUT_US: u32 = 50_000;
+pub(crate) const SOFT_RESET_TIME_US: u32 = 10;
+pub(crate) const BOOT_TIME_MS: u32 = 20;
+#[cfg(feature = "float")]
 const LIS2MDL_MAG_LSB: f32 = 1.5; // mgauss/LSB
+#[cfg(feature = "float")]
 const LIS2MDL_MILLIGAUSS_TO_MICROTESLA: f32 = 0.1; // 1 mgauss = 0.1 microtesla
+#[cfg(feature = "float")]
+const LIS2MDL_TEMP_LSB: f32 = 8.0; // LSB/°C
+#[cfg(feature = "float")]
+const LIS2MDL_TEMP_OFFSET: f32 = 25.0; // °C at zero output
 
 #[derive(Debug)]
-pub struct Lis2mdl<I2C, DELAY> {
-    pub(crate) i2c: I2C,
-    pub(crate) address: u8,
+pub struct Lis2mdl<IFACE, DELAY> {
+    pub(crate) iface: IFACE,
     pub(crate) delay: DELAY,
+    pub(crate) config: Config,
     pub mag_x: i16,
     pub mag_y: i16,
     pub mag_z: i16,
-    pub x_min: f32,
-    pub x_max: f32,
-    pub y_min: f32,
-    pub y_max: f32,
+    #[cfg(feature = "float")]
+    pub(crate) calibration: Calibration,
+    #[cfg(feature = "float")]
+    pub(crate) declination: f32,
+    pub(crate) orientation: Orientation,
+    // applied with `apply_sensor_offset`, chip axes
+    pub(crate) sensor_offset: [i16; 3],
 }
 
 #[derive(Debug)]
 pub enum Error<E> {
     // I²C bus error
     I2C(E),
+    // SPI bus error
+    Spi(E),
+    // INT/DRDY input pin error
+    Pin,
+    // WHO_AM_I did not match CHIP_ID
+    WrongChipId(u8),
+    // no new data before the timeout expired
+    Timeout,
 }
 
-impl<I2C, DELAY, E> Lis2mdl<I2C, DELAY>
-where
-    DELAY: DelayNs,
-    I2C: embedded_hal::i2c::I2c<Error = E>,
-{
+impl<I2C, DELAY> Lis2mdl<I2cInterface<I2C>, DELAY> {
     pub fn new<A: Into<Address>>(i2c: I2C, address: A, delay: DELAY) -> Self {
-        let a = address.into();
+        Self::with_interface(I2cInterface::new(i2c, address), delay)
+    }
+}
+
+impl<SPI, DELAY> Lis2mdl<SpiInterface<SPI>, DELAY> {
+    pub fn new_spi(spi: SPI, wires: SpiWires, delay: DELAY) -> Self {
+        Self::with_interface(SpiInterface::new(spi, wires), delay)
+    }
+}
+
+impl<IFACE, DELAY> Lis2mdl<IFACE, DELAY> {
+    pub fn with_interface(iface: IFACE, delay: DELAY) -> Self {
         Lis2mdl {
-            i2c,
-            address: a.0,
+            iface,
             delay,
+            config: Config::default(),
             mag_x: 0,
             mag_y: 0,
             mag_z: 0,
-            x_min: f32::MAX,
-            x_max: f32::MIN,
-            y_min: f32::MAX,
-            y_max: f32::MIN,
+            #[cfg(feature = "float")]
+            calibration: Calibration::new(),
+            #[cfg(feature = "float")]
+            declination: 0.0,
+            orientation: Orientation::IDENTITY,
+            sensor_offset: [0; 3],
         }
     }
 
+    /// Use `config` instead of the default when `start` is called.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// How the chip is mounted relative to the device frame. Applied to
+    /// `current_xyz` and everything built on it; `mag_x/y/z` stay raw.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    pub fn release(self) -> (IFACE, DELAY) {
+        (self.iface, self.delay)
+    }
+}
+
+impl<IFACE, DELAY, E> Lis2mdl<IFACE, DELAY>
+where
+    DELAY: DelayNs,
+    IFACE: Interface<Error = E>,
+{
     pub fn start(&mut self) -> Result<(), Error<E>> {
         // self.set_register(LIS2MDL_CFG_REG_A, 0x8C)?;
         // self.delay.delay_ns(10_000);
         // self.set_register(LIS2MDL_CFG_REG_C, 0x11)?;
         // self.delay.delay_ns(10_000);
-        // First: ensure I²C mode by explicitly clearing I2C_DISABLE
-        self.set_register(LIS2MDL_CFG_REG_C, 0x00)?; // I²C enabled, auto-inc off
-        self.delay.delay_ns(5000);
+        // First: make sure we are talking to a LIS2MDL before configuring it
+        self.probe()?;
 
-        // Now enable auto-increment + BDU
-        self.set_register(LIS2MDL_CFG_REG_C, 0x11)?; // IF_ADD_INC = 1, BDU = 1
+        // Now select the bus (I2C_DIS and 4WSPI, 0x00 for I²C) and enable BDU
+        let interface = self.iface.cfg_reg_c();
+        self.write_reg(interface.with_bdu(true).with_drdy_on_pin(true))?; // 0x11 for I²C
         self.delay.delay_ns(5000);
 
-        // Finally, set reliable ODR + temp comp
-        self.set_register(LIS2MDL_CFG_REG_A, 0x00)?; // continuous mode, ODR default
+        // Finally, apply ODR, mode and filtering
+        self.configure(self.config)
+    }
+
+    /// Check WHO_AM_I, then soft reset and reboot, then `start`.
+    pub fn init(&mut self) -> Result<(), Error<E>> {
+        self.probe()?;
+        self.reset()?;
+        self.start()
+    }
+
+    // Verify WHO_AM_I before writing anything else. Only 4-wire SPI needs a
+    // write first: the device does not drive SDO until 4WSPI is set.
+    fn probe(&mut self) -> Result<(), Error<E>> {
+        let interface = self.iface.cfg_reg_c();
+        if interface.four_wire_spi() {
+            self.write_reg(interface)?;
+            self.delay.delay_ns(5000);
+        }
+
+        self.verify_chip_id()
+    }
+
+    /// Reset the configuration registers (SOFT_RST) and reload the trimming
+    /// parameters from flash (REBOOT). Leaves the device idle.
+    pub fn reset(&mut self) -> Result<(), Error<E>> {
+        let idle = CfgRegA::default().with_mode(Mode::Idle);
+
+        self.write_reg(idle.with_soft_rst(true))?;
+        self.delay.delay_us(SOFT_RESET_TIME_US);
+
+        self.write_reg(idle.with_reboot(true))?;
+        self.delay.delay_ms(BOOT_TIME_MS);
+
+        // SOFT_RST cleared the OFFSET registers
+        #[cfg(feature = "float")]
+        self.calibration.set_device_offset([0.0; 3]);
+        self.sensor_offset = [0; 3];
+
+        Ok(())
+    }
+
+    /// Check WHO_AM_I against `CHIP_ID`.
+    pub fn verify_chip_id(&mut self) -> Result<(), Error<E>> {
+        match self.whoami()? {
+            CHIP_ID => Ok(()),
+            id => Err(Error::WrongChipId(id)),
+        }
+    }
+
+    /// Apply a measurement configuration.
+    ///
+    /// `CFG_REG_B` is written before `CFG_REG_A` so the new operating mode
+    /// only starts once the filter and offset settings are in place.
+    pub fn configure(&mut self, config: Config) -> Result<(), Error<E>> {
+        self.write_reg(config.cfg_reg_b())?;
         self.delay.delay_ns(5000);
 
-        self.set_register(LIS2MDL_CFG_REG_B, 0x00)?;
+        self.write_reg(config.cfg_reg_a())?;
         self.delay.delay_ns(10_000);
 
+        self.config = config;
         Ok(())
     }
 
-    pub fn whoami(&mut self) -> Result<u8, Error<E>> {
-        let mut buffer = [0u8; 1];
-        let mut operations = [
-            Operation::Write(&[LIS2MDL_WHO_AM_I_REG]),
-            Operation::Read(&mut buffer),
-        ];
-        self.i2c
-            .transaction(self.address, &mut operations)
-            .map_err(Error::I2C)?;
+    /// The configuration last applied with `configure` (or the default).
+    pub fn config(&self) -> Config {
+        self.config
+    }
 
-        Ok(buffer[0])
+    /// Read the configuration currently held by the device.
+    pub fn read_config(&mut self) -> Result<Config, Error<E>> {
+        let a = self.read_reg::<CfgRegA>()?;
+        let b = self.read_reg::<CfgRegB>()?;
+
+        Ok(Config::from_registers(a, b))
+    }
+
+    pub fn whoami(&mut self) -> Result<u8, Error<E>> {
+        self.get_register(Register::WhoAmI.addr())
     }
 
     pub fn get_register(&mut self, reg: u8) -> Result<u8, Error<E>> {
         let mut buffer = [0u8; 1];
-        let mut operations = [Operation::Write(&[reg]), Operation::Read(&mut buffer)];
-        self.i2c
-            .transaction(self.address, &mut operations)
-            .map_err(Error::I2C)?;
+        self.read_registers(reg, &mut buffer)?;
 
         Ok(buffer[0])
     }
 
     pub fn set_register(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
-        self.i2c
-            .write(self.address, &[reg, value])
-            .map_err(Error::I2C)
+        self.iface.write_registers(reg, &[value])
+    }
+
+    /// Burst-read consecutive registers starting at `reg`.
+    pub fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        self.iface.read_registers(reg, buffer)
+    }
+
+    /// Read a register and decode it into its bitfield type.
+    pub fn read_reg<R: RegisterBits>(&mut self) -> Result<R, Error<E>> {
+        self.get_register(R::REGISTER.addr()).map(R::from_bits)
+    }
+
+    pub fn write_reg<R: WritableRegister>(&mut self, value: R) -> Result<(), Error<E>> {
+        self.set_register(R::REGISTER.addr(), value.bits())
+    }
+
+    /// Read-modify-write a register, returning the value written.
+    pub fn modify_reg<R: WritableRegister, F: FnOnce(R) -> R>(
+        &mut self,
+        f: F,
+    ) -> Result<R, Error<E>> {
+        let value = f(self.read_reg::<R>()?);
+        self.write_reg(value)?;
+
+        Ok(value)
+    }
+
+    /// Read a little-endian 16-bit register pair starting at its low byte.
+    pub fn read_reg16(&mut self, low: Register) -> Result<i16, Error<E>> {
+        let mut buffer = [0u8; 2];
+        self.read_registers(low.addr(), &mut buffer)?;
+
+        Ok(i16::from_le_bytes(buffer))
     }
 
+    /// Write a little-endian 16-bit register pair starting at its low byte.
+    pub fn write_reg16(&mut self, low: Register, value: i16) -> Result<(), Error<E>> {
+        self.iface.write_registers(low.addr(), &value.to_le_bytes())
+    }
+
+    #[cfg(feature = "float")]
     pub fn current_xyz(&mut self) -> (f32, f32, f32) {
-        let x = self.mag_x as f32 * LIS2MDL_MAG_LSB * LIS2MDL_MILLIGAUSS_TO_MICROTESLA;
-        let y = self.mag_y as f32 * LIS2MDL_MAG_LSB * LIS2MDL_MILLIGAUSS_TO_MICROTESLA;
-        let z = self.mag_z as f32 * LIS2MDL_MAG_LSB * LIS2MDL_MILLIGAUSS_TO_MICROTESLA;
+        let (x, y, z) = self
+            .orientation
+            .apply_raw((self.mag_x, self.mag_y, self.mag_z));
+        let x = raw_to_microtesla(x);
+        let y = raw_to_microtesla(y);
+        let z = raw_to_microtesla(z);
 
         (x, y, z)
     }
 
+    #[cfg(feature = "float")]
+    /// The last sample in the device frame, uncalibrated.
+    pub fn magnetic_field(&mut self) -> MagneticField {
+        let (x, y, z) = self.current_xyz();
+        MagneticField::from_microtesla(x, y, z)
+    }
+
+    #[cfg(feature = "float")]
+    /// The last sample in the device frame with the calibration applied.
+    pub fn calibrated_field(&mut self) -> MagneticField {
+        let (x, y, z) = self.calibrated_xyz();
+        MagneticField::from_microtesla(x, y, z)
+    }
+
+    #[cfg(feature = "float")]
+    /// Typed `get_heading`.
+    pub fn heading(&mut self) -> Heading {
+        Heading::from_degrees(self.get_heading())
+    }
+
+    #[cfg(feature = "float")]
     pub fn get_heading(&mut self) -> f32 {
-        let (x, y, _z) = self.current_xyz();
+        let (x, y, _z) = self.calibrated_xyz();
 
-        // save min/max for calibration
-        self.x_max = self.x_max.max(x);
-        self.x_min = self.x_min.min(x);
-        self.y_max = self.y_max.max(y);
-        self.y_min = self.y_min.min(y);
+        heading::heading_from_xy(x, y)
+    }
 
-        // hard-iron calibration
-        let x_offset = (self.x_max + self.x_min) / 2.0;
-        let y_offset = (self.y_max + self.y_min) / 2.0;
+    /// Read and decode STATUS_REG.
+    pub fn status(&mut self) -> Result<StatusReg, Error<E>> {
+        self.read_reg::<StatusReg>()
+    }
 
-        // apply calibration offsets
-        let x = x - x_offset;
-        let y = y - y_offset;
+    /// Whether a new X/Y/Z sample is available (ZYXDA).
+    pub fn data_ready(&mut self) -> Result<bool, Error<E>> {
+        self.status().map(|status| status.zyxda())
+    }
 
-        let heading = y.atan2(x) * 180.0 / core::f32::consts::PI;
-        if heading < 0.0 {
-            heading + 360.0
-        } else {
-            heading
+    /// Poll STATUS_REG until ZYXDA is set, or fail with `Error::Timeout`
+    /// once `timeout_us` has elapsed.
+    pub fn wait_for_data(&mut self, timeout_us: u32) -> Result<StatusReg, Error<E>> {
+        let mut waited = 0;
+        loop {
+            let status = self.status()?;
+            if status.zyxda() {
+                return Ok(status);
+            }
+            if waited >= timeout_us {
+                return Err(Error::Timeout);
+            }
+            self.delay.delay_us(DELAY_TIME);
+            waited = waited.saturating_add(DELAY_TIME);
         }
     }
 
+    /// Wait for a new sample and `read` it. The returned status carries the
+    /// overrun flags, which are set when a sample was missed.
+    pub fn read_when_ready(&mut self, timeout_us: u32) -> Result<StatusReg, Error<E>> {
+        let status = self.wait_for_data(timeout_us)?;
+        self.read()?;
+
+        Ok(status)
+    }
+
+    /// Trigger a single measurement, wait for it and `read` it.
+    ///
+    /// Uses the ODR, power mode and filtering from the current `config`.
+    /// The device returns to idle once the conversion completes.
+    pub fn measure_once(&mut self) -> Result<StatusReg, Error<E>> {
+        // stop continuous conversions, then drain any sample they left so
+        // ZYXDA only comes from this conversion
+        let a = self.config.cfg_reg_a();
+        self.write_reg(a.with_mode(Mode::Idle))?;
+        let mut buffer = [0u8; 6];
+        self.read_registers(Register::OutxL.addr(), &mut buffer)?;
+        self.write_reg(a.with_mode(Mode::Single))?;
+
+        // allow two output periods before giving up
+        let status = self.wait_for_data(2 * self.config.sample_period_us())?;
+        self.read()?;
+
+        Ok(status)
+    }
+
     pub fn read(&mut self) -> Result<(), Error<E>> {
         let mut buffer = [0u8; 6];
-        let mut operations = [
-            Operation::Write(&[LIS2MDL_OUTX_L_REG]),
-            Operation::Read(&mut buffer),
-        ];
-        self.i2c
-            .transaction(self.address, &mut operations)
-            .map_err(Error::I2C)?;
+        self.read_registers(Register::OutxL.addr(), &mut buffer)?;
 
-        self.mag_x = i16::from_le_bytes([buffer[0], buffer[1]]);
-        self.mag_y = i16::from_le_bytes([buffer[2], buffer[3]]);
-        self.mag_z = i16::from_le_bytes([buffer[4], buffer[5]]);
+        self.store_sample(decode_xyz(&buffer));
 
         Ok(())
     }
+
+    // Discard `settle` fresh samples, then sum the next `samples` straight
+    // from the output registers, bypassing the cache and the calibration
+    pub(crate) fn sum_raw(&mut self, settle: u32, samples: u32) -> Result<[i64; 3], Error<E>> {
+        let mut sum = [0i64; 3];
+        for i in 0..settle + samples {
+            self.wait_for_data(AVERAGE_TIMEOUT_US)?;
+            let mut buffer = [0u8; 6];
+            self.read_registers(Register::OutxL.addr(), &mut buffer)?;
+
+            if i >= settle {
+                let (x, y, z) = decode_xyz(&buffer);
+                sum[0] += x as i64;
+                sum[1] += y as i64;
+                sum[2] += z as i64;
+            }
+        }
+        Ok(sum)
+    }
+
+    // Cache a new sample and feed it to the calibration if collecting
+    fn store_sample(&mut self, xyz: (i16, i16, i16)) {
+        (self.mag_x, self.mag_y, self.mag_z) = xyz;
+
+        #[cfg(feature = "float")]
+        {
+            let xyz = self.current_xyz();
+            self.calibration.update(xyz);
+        }
+    }
+
+    #[cfg(feature = "float")]
+    /// Die temperature in °C.
+    pub fn read_temperature(&mut self) -> Result<f32, Error<E>> {
+        self.read_reg16(Register::TempOutL).map(raw_to_celsius)
+    }
+
+    #[cfg(feature = "float")]
+    /// Like `read`, but also returns the die temperature in °C from the
+    /// same burst (OUTX_L..TEMP_OUT_H).
+    pub fn read_with_temperature(&mut self) -> Result<f32, Error<E>> {
+        let mut buffer = [0u8; 8];
+        self.read_registers(Register::OutxL.addr(), &mut buffer)?;
+
+        self.store_sample(decode_xyz(&buffer));
+
+        Ok(raw_to_celsius(i16::from_le_bytes([buffer[6], buffer[7]])))
+    }
+}
+
+#[cfg(feature = "float")]
+pub(crate) fn raw_to_microtesla(raw: i16) -> f32 {
+    raw as f32 * LIS2MDL_MAG_LSB * LIS2MDL_MILLIGAUSS_TO_MICROTESLA
+}
+
+#[cfg(feature = "float")]
+pub(crate) fn raw_to_celsius(raw: i16) -> f32 {
+    raw as f32 / LIS2MDL_TEMP_LSB + LIS2MDL_TEMP_OFFSET
+}
+
+// OUTX_L..OUTZ_H, little-endian
+pub(crate) fn decode_xyz(buffer: &[u8]) -> (i16, i16, i16) {
+    (
+        i16::from_le_bytes([buffer[0], buffer[1]]),
+        i16::from_le_bytes([buffer[2], buffer[3]]),
+        i16::from_le_bytes([buffer[4], buffer[5]]),
+    )
 }
 
 // I2C device address
@@ -182,5 +492,1153 @@ impl Address {
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
+
+    use std::vec;
+    use std::vec::Vec;
+
+    use embedded_hal::digital::InputPin;
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
     use super::*;
+
+    #[test]
+    fn cfg_reg_a_fields() {
+        let reg = CfgRegA::from_bits(0x8C);
+        assert!(reg.comp_temp_en());
+        assert!(!reg.lp());
+        assert_eq!(reg.odr(), OutputDataRate::Hz100);
+        assert_eq!(reg.mode(), Mode::Continuous);
+
+        let reg = reg
+            .with_mode(Mode::Single)
+            .with_odr(OutputDataRate::Hz20)
+            .with_lp(true);
+        assert_eq!(reg.bits(), 0x95);
+    }
+
+    #[test]
+    fn cfg_reg_c_fields() {
+        let reg = CfgRegC::default().with_bdu(true).with_drdy_on_pin(true);
+        assert_eq!(reg.bits(), 0x11);
+        assert_eq!(reg.with_drdy_on_pin(false).bits(), 0x10);
+    }
+
+    #[test]
+    fn temperature_scale() {
+        #[cfg(feature = "float")]
+        {
+            assert_eq!(raw_to_celsius(0), 25.0);
+            assert_eq!(raw_to_celsius(8), 26.0);
+            assert_eq!(raw_to_celsius(-40), 20.0);
+        }
+        assert_eq!(fixed::raw_to_millicelsius(-40), 20_000);
+        assert_eq!(fixed::raw_to_millicelsius(1), 25_125);
+    }
+
+    #[test]
+    fn fixed_point() {
+        assert_eq!(fixed::raw_to_nanotesla(-32768), -4_915_200);
+        assert_eq!(fixed::heading_centidegrees(0, 0), 0);
+
+        for (x, y) in [
+            (1, 0),
+            (0, 1),
+            (-300, 0),
+            (0, -2),
+            (1000, 1),
+            (-7, 5),
+            (32767, -32768),
+        ] {
+            let expected = (y as f64).atan2(x as f64).to_degrees().rem_euclid(360.0) * 100.0;
+            let heading = fixed::heading_centidegrees(x, y) as f64;
+            let error = (heading - expected).abs();
+            assert!(error <= 1.0 || error >= 35_999.0, "{x} {y} {heading}");
+        }
+        for degrees in (0..3600).map(|d| (d as f64 / 10.0).to_radians()) {
+            let (x, y) = (
+                (degrees.cos() * 500.0) as i32,
+                (degrees.sin() * 500.0) as i32,
+            );
+            let expected = (y as f64).atan2(x as f64).to_degrees().rem_euclid(360.0) * 100.0;
+            let error = (fixed::heading_centidegrees(x, y) as f64 - expected).abs();
+            assert!(error <= 1.0 || error >= 35_999.0);
+        }
+    }
+
+    #[test]
+    fn interrupt_registers() {
+        let config = InterruptConfig::new()
+            .with_axes(true, false, true)
+            .with_active_high(true)
+            .with_latched(true);
+        assert_eq!(config.int_ctrl_reg().bits(), 0xA7);
+        assert_eq!(
+            InterruptConfig::from_register(config.int_ctrl_reg()),
+            config
+        );
+
+        let source = InterruptSource::from(IntSourceReg::from_bits(0x8B));
+        assert_eq!(source.positive, [true, false, false]);
+        assert_eq!(source.negative, [false, true, false]);
+        assert!(source.overflow && source.triggered);
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn ellipsoid_fit_recovers_distortion() {
+        use core::f64::consts::{FRAC_PI_2, FRAC_PI_6};
+
+        let offset = [12.0f64, -30.0, 5.0];
+        let radii = [55.0f64, 40.0, 48.0];
+
+        let mut fit = EllipsoidFit::new();
+        for i in 0..12 {
+            for j in 1..12 {
+                let (lon, lat) = (i as f64 * FRAC_PI_6, j as f64 * FRAC_PI_6 / 2.0 - FRAC_PI_2);
+                fit.add_sample((
+                    (offset[0] + radii[0] * lat.cos() * lon.cos()) as f32,
+                    (offset[1] + radii[1] * lat.cos() * lon.sin()) as f32,
+                    (offset[2] + radii[2] * lat.sin()) as f32,
+                ));
+            }
+        }
+
+        let cal = fit.fit().unwrap();
+        for (h, o) in cal.hard_iron.iter().zip(offset) {
+            assert!((*h as f64 - o).abs() < 0.01);
+        }
+        assert!(cal.residual < 1e-4);
+        assert!(cal.coverage > 0.95);
+
+        // every corrected sample lands on the same sphere
+        let (x, y, z) = cal.apply((12.0, 10.0, 5.0));
+        let r = (x * x + y * y + z * z).sqrt();
+        assert!((r - cal.field_strength).abs() < 0.01);
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn calibration_only_learns_while_collecting() {
+        let mut cal = Calibration::new();
+        cal.update((10.0, 10.0, 10.0));
+        assert_eq!(cal.hard_iron, [0.0; 3]);
+
+        cal.begin();
+        cal.update((10.0, -20.0, 0.0));
+        cal.update((30.0, 20.0, 4.0));
+        cal.finish();
+        cal.update((500.0, 500.0, 500.0));
+        assert_eq!(cal.hard_iron, [20.0, 0.0, 2.0]);
+        assert_eq!(cal.apply((25.0, 5.0, 2.0)), (5.0, 5.0, 0.0));
+
+        cal.reset();
+        assert_eq!(cal, Calibration::new());
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn calibration_bytes_round_trip() {
+        let cal = Calibration::new()
+            .with_hard_iron([1.5, -2.25, 40.0])
+            .with_soft_iron([[1.1, 0.1, 0.0], [0.1, 0.9, 0.0], [0.0, 0.0, 1.0]])
+            .with_temperature(23.5)
+            .with_sequence(42);
+        let bytes = cal.to_bytes();
+        assert_eq!(&bytes[..4], &[b'L', b'M', 1, 0b11]);
+        assert_eq!(Calibration::from_bytes(&bytes), Ok(cal));
+
+        let plain = Calibration::new().with_hard_iron([3.0, 4.0, 5.0]);
+        assert_eq!(Calibration::from_bytes(&plain.to_bytes()), Ok(plain));
+
+        let mut corrupt = bytes;
+        corrupt[10] ^= 0x01;
+        assert_eq!(
+            Calibration::from_bytes(&corrupt),
+            Err(DecodeError::Checksum)
+        );
+        assert_eq!(
+            Calibration::from_bytes(&bytes[..63]),
+            Err(DecodeError::Length)
+        );
+        // CRC-32 check value
+        assert_eq!(calibration::crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn tilt_compensation_matches_level_heading() {
+        let mag = (20.0, 20.0, -40.0);
+        let level = heading::heading_from_xy(mag.0, mag.1);
+        let (pitch, roll) = heading::pitch_roll((0.0, 0.0, 1.0));
+        let (xh, yh) = heading::tilt_compensate(mag, pitch, roll);
+        assert!((heading::heading_from_xy(xh, yh) - level).abs() < 0.5);
+
+        // rolled 90° about X: Y points up and Z along the old -Y
+        let (pitch, roll) = heading::pitch_roll((0.0, 1.0, 0.0));
+        assert!((roll - 90.0).abs() < 0.5 && pitch.abs() < 0.5);
+        let (xh, yh) = heading::tilt_compensate((20.0, -40.0, -20.0), pitch, roll);
+        assert!((heading::heading_from_xy(xh, yh) - level).abs() < 1.0);
+
+        assert_eq!(heading::magnetic_to_true(355.0, 10.0), 5.0);
+        assert_eq!(heading::magnetic_to_true(5.0, -10.0), 355.0);
+    }
+
+    #[cfg(feature = "wmm")]
+    #[test]
+    fn wmm_declination() {
+        let model = wmm::WMM2025;
+        // Boulder, Sydney, Oslo and London against the NOAA calculator
+        for (lat, lon, expected) in [
+            (40.0, -105.0, 7.5),
+            (-33.9, 151.2, 12.9),
+            (59.9, 10.75, 4.5),
+            (51.5, -0.13, 1.0),
+        ] {
+            let declination = model.declination(lat, lon, 2026.0);
+            assert!((declination - expected).abs() < 0.5, "{declination}");
+        }
+    }
+
+    #[test]
+    fn orientations() {
+        let all = Orientation::all();
+        for (i, a) in all.iter().enumerate() {
+            assert!(all[i + 1..].iter().all(|b| a != b));
+        }
+        assert!(Orientation::new(Direction::PlusX, Direction::PlusY, Direction::MinusZ).is_none());
+        assert!(Orientation::new(Direction::PlusX, Direction::PlusX, Direction::PlusZ).is_none());
+
+        let raw = (100, 200, i16::MIN);
+        assert_eq!(
+            Orientation::ROTATE_Z_90.apply_raw(raw),
+            (-200, 100, i16::MIN)
+        );
+        assert_eq!(Orientation::BOTTOM.apply_raw(raw), (100, -200, i16::MAX));
+        assert_eq!(
+            Orientation::ROTATE_Z_90.then(Orientation::ROTATE_Z_90),
+            Orientation::ROTATE_Z_180
+        );
+        assert_eq!(
+            Orientation::ROTATE_Z_90.then(Orientation::ROTATE_Z_270),
+            Orientation::IDENTITY
+        );
+        for orientation in all {
+            assert_eq!(
+                orientation.then(orientation.inverse()),
+                Orientation::IDENTITY
+            );
+        }
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn units() {
+        let field = MagneticField::from_raw((100, -200, 0));
+        assert_eq!(field.microtesla(), (15.0, -30.0, 0.0));
+        assert_eq!(field.milligauss(), (150.0, -300.0, 0.0));
+        let (gx, gy, _) = field.gauss();
+        assert!((gx - 0.15).abs() < 1e-6 && (gy + 0.3).abs() < 1e-6);
+        assert_eq!(field.raw_lsb(), (100.0, -200.0, 0.0));
+
+        let heading = Heading::from_degrees(-30.0);
+        assert_eq!(heading.degrees(), 330.0);
+        assert_eq!(heading.cardinal(), Cardinal::NW);
+        assert_eq!(Heading::from_degrees(359.0).cardinal(), Cardinal::N);
+        assert_eq!(Heading::from_degrees(100.0).cardinal().abbreviation(), "E");
+    }
+
+    #[test]
+    fn measurement_decoding() {
+        let bytes = [0x0F, 0x64, 0x00, 0x38, 0xFF, 0x00, 0x80];
+        let measurement = Measurement::from_bytes(&bytes);
+        assert_eq!(measurement.raw, (100, -200, -32768));
+        assert!(measurement.is_new());
+        assert!(!measurement.overrun());
+        assert_eq!(measurement.nanotesla(), (15_000, -30_000, -4_915_200));
+        assert!(Measurement::from_bytes(&[0x80, 0, 0, 0, 0, 0, 0]).overrun());
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let config = Config::new()
+            .with_odr(OutputDataRate::Hz100)
+            .with_power_mode(PowerMode::LowPower)
+            .with_temperature_compensation(true)
+            .with_low_pass_filter(true);
+        assert_eq!(config.cfg_reg_a().bits(), 0x9C);
+        assert_eq!(config.cfg_reg_b().bits(), 0x01);
+        assert_eq!(
+            Config::from_registers(config.cfg_reg_a(), config.cfg_reg_b()),
+            config
+        );
+        assert_eq!(Config::default().cfg_reg_a().bits(), 0x00);
+        assert_eq!(Config::default().cfg_reg_b().bits(), 0x00);
+    }
+
+    #[test]
+    fn cfg_reg_b_fields() {
+        let b = |config: Config| config.cfg_reg_b().bits();
+        let config = Config::new();
+        assert_eq!(b(config.with_low_pass_filter(true)), 0x01);
+        assert_eq!(b(config.with_offset_cancellation(true)), 0x02);
+        assert_eq!(b(config.with_set_pulse(SetPulse::PowerOnOnly)), 0x04);
+        assert_eq!(b(config.with_interrupt_on_corrected_data(true)), 0x08);
+        assert_eq!(b(config.with_offset_cancellation_one_shot(true)), 0x10);
+
+        let all = config
+            .with_low_pass_filter(true)
+            .with_offset_cancellation(true)
+            .with_set_pulse(SetPulse::PowerOnOnly)
+            .with_interrupt_on_corrected_data(true)
+            .with_offset_cancellation_one_shot(true);
+        assert_eq!(b(all), 0x1F);
+        assert_eq!(
+            Config::from_registers(all.cfg_reg_a(), all.cfg_reg_b()),
+            all
+        );
+
+        // configure writes them to CFG_REG_B, before CFG_REG_A
+        let expected = [write(&[0x61, 0x1F]), write(&[0x60, 0x00])];
+        with_bus(&expected, |s| s.configure(all).unwrap());
+    }
+
+    // Bus-level tests: exact I²C transactions for each driver method
+
+    const ADDR: u8 = DEFAULT_DEVICE_ID;
+
+    // register address, then the data, in one transaction
+    fn write(data: &[u8]) -> Vec<I2cTransaction> {
+        vec![
+            I2cTransaction::transaction_start(ADDR),
+            I2cTransaction::write(ADDR, vec![data[0]]),
+            I2cTransaction::write(ADDR, data[1..].to_vec()),
+            I2cTransaction::transaction_end(ADDR),
+        ]
+    }
+
+    fn read(reg: u8, response: &[u8]) -> Vec<I2cTransaction> {
+        vec![
+            I2cTransaction::transaction_start(ADDR),
+            I2cTransaction::write(ADDR, vec![reg]),
+            I2cTransaction::read(ADDR, response.to_vec()),
+            I2cTransaction::transaction_end(ADDR),
+        ]
+    }
+
+    fn start_sequence() -> Vec<I2cTransaction> {
+        [
+            read(0x4F, &[CHIP_ID]),
+            write(&[0x62, 0x11]),
+            write(&[0x61, 0x00]),
+            write(&[0x60, 0x00]),
+        ]
+        .concat()
+    }
+
+    // Run `f` against a driver expecting exactly `expected`
+    fn with_bus<T>(
+        expected: &[Vec<I2cTransaction>],
+        f: impl FnOnce(&mut Lis2mdl<I2cInterface<I2cMock>, NoopDelay>) -> T,
+    ) -> T {
+        let mut i2c = I2cMock::new(&expected.concat());
+        let mut sensor = Lis2mdl::new(i2c.clone(), Address::default(), NoopDelay);
+        let result = f(&mut sensor);
+        i2c.done();
+        result
+    }
+
+    // Fails every access, for read paths the mock cannot fail
+    struct FailingI2c;
+
+    impl embedded_hal::i2c::ErrorType for FailingI2c {
+        type Error = ErrorKind;
+    }
+
+    impl embedded_hal::i2c::I2c for FailingI2c {
+        fn transaction(
+            &mut self,
+            _address: u8,
+            _operations: &mut [embedded_hal::i2c::Operation<'_>],
+        ) -> Result<(), ErrorKind> {
+            Err(ErrorKind::Bus)
+        }
+    }
+
+    // Passes the first `ok` transactions to the mock, then fails with
+    // `ErrorKind::Other`; the mock panics on errors inside a transaction
+    struct FailAfter {
+        i2c: I2cMock,
+        ok: usize,
+    }
+
+    impl embedded_hal::i2c::ErrorType for FailAfter {
+        type Error = ErrorKind;
+    }
+
+    impl embedded_hal::i2c::I2c for FailAfter {
+        fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [embedded_hal::i2c::Operation<'_>],
+        ) -> Result<(), ErrorKind> {
+            if self.ok == 0 {
+                return Err(ErrorKind::Other);
+            }
+            self.ok -= 1;
+            self.i2c.transaction(address, operations)
+        }
+    }
+
+    // Like `with_bus`, but the access after `expected` fails
+    fn with_failing_bus<T>(
+        expected: &[Vec<I2cTransaction>],
+        f: impl FnOnce(&mut Lis2mdl<I2cInterface<FailAfter>, NoopDelay>) -> T,
+    ) -> T {
+        let mut i2c = I2cMock::new(&expected.concat());
+        let bus = FailAfter {
+            i2c: i2c.clone(),
+            ok: expected.len(),
+        };
+        let mut sensor = Lis2mdl::new(bus, Address::default(), NoopDelay);
+        let result = f(&mut sensor);
+        i2c.done();
+        result
+    }
+
+    #[test]
+    fn bus_start() {
+        with_bus(&[start_sequence()], |s| s.start().unwrap());
+
+        let config = Config::new()
+            .with_odr(OutputDataRate::Hz100)
+            .with_temperature_compensation(true)
+            .with_low_pass_filter(true);
+        let expected = [
+            read(0x4F, &[CHIP_ID]),
+            write(&[0x62, 0x11]),
+            write(&[0x61, 0x01]),
+            write(&[0x60, 0x8C]),
+        ];
+        let mut i2c = I2cMock::new(&expected.concat());
+        let mut sensor =
+            Lis2mdl::new(i2c.clone(), Address::default(), NoopDelay).with_config(config);
+        sensor.start().unwrap();
+        assert_eq!(sensor.config(), config);
+        i2c.done();
+    }
+
+    #[test]
+    fn bus_start_wrong_chip_id() {
+        let result = with_bus(&[read(0x4F, &[0x3D])], |s| s.start());
+        assert!(matches!(result, Err(Error::WrongChipId(0x3D))));
+    }
+
+    #[test]
+    fn bus_init_and_reset() {
+        let reset = [write(&[0x60, 0x23]), write(&[0x60, 0x43])].concat();
+        with_bus(core::slice::from_ref(&reset), |s| s.reset().unwrap());
+        with_bus(&[read(0x4F, &[CHIP_ID]), reset, start_sequence()], |s| {
+            s.init().unwrap()
+        });
+
+        // nothing is written to a device that is not a LIS2MDL
+        let result = with_bus(&[read(0x4F, &[0x3D])], |s| s.init());
+        assert!(matches!(result, Err(Error::WrongChipId(0x3D))));
+    }
+
+    #[test]
+    fn bus_register_access() {
+        let id = with_bus(&[read(0x4F, &[CHIP_ID])], |s| s.whoami().unwrap());
+        assert_eq!(id, CHIP_ID);
+        with_bus(&[read(0x4F, &[CHIP_ID])], |s| s.verify_chip_id().unwrap());
+
+        let value = with_bus(&[read(0x63, &[0xE5])], |s| s.get_register(0x63).unwrap());
+        assert_eq!(value, 0xE5);
+        with_bus(&[write(&[0x63, 0xE5])], |s| {
+            s.set_register(0x63, 0xE5).unwrap()
+        });
+
+        let mut buffer = [0u8; 3];
+        with_bus(&[read(0x45, &[1, 2, 3])], |s| {
+            s.read_registers(0x45, &mut buffer).unwrap()
+        });
+        assert_eq!(buffer, [1, 2, 3]);
+
+        let status = with_bus(&[read(0x67, &[0x88])], |s| {
+            s.read_reg::<StatusReg>().unwrap()
+        });
+        assert!(status.zyxda() && status.zyxor());
+        with_bus(&[write(&[0x60, 0x8C])], |s| {
+            s.write_reg(CfgRegA::from_bits(0x8C)).unwrap()
+        });
+
+        let written = with_bus(&[read(0x62, &[0x11]), write(&[0x62, 0x51])], |s| {
+            s.modify_reg::<CfgRegC, _>(|reg| reg.with_int_on_pin(true))
+                .unwrap()
+        });
+        assert_eq!(written.bits(), 0x51);
+
+        let value = with_bus(&[read(0x6E, &[0x38, 0xFF])], |s| {
+            s.read_reg16(Register::TempOutL).unwrap()
+        });
+        assert_eq!(value, -200);
+        with_bus(&[write(&[0x65, 0x38, 0xFF])], |s| {
+            s.write_reg16(Register::IntThsL, -200).unwrap()
+        });
+    }
+
+    #[test]
+    fn bus_configure() {
+        let config = Config::new()
+            .with_odr(OutputDataRate::Hz50)
+            .with_mode(Mode::Idle)
+            .with_offset_cancellation(true);
+        with_bus(&[write(&[0x61, 0x02]), write(&[0x60, 0x0B])], |s| {
+            s.configure(config).unwrap();
+            assert_eq!(s.config(), config);
+        });
+
+        let read_back = with_bus(&[read(0x60, &[0x0B]), read(0x61, &[0x02])], |s| {
+            s.read_config().unwrap()
+        });
+        assert_eq!(read_back, config);
+    }
+
+    #[test]
+    fn bus_read_byte_order() {
+        // OUTX_L first, little-endian pairs
+        let data = [0x01, 0x02, 0x38, 0xFF, 0xFF, 0x7F];
+        with_bus(&[read(0x68, &data)], |s| {
+            s.read().unwrap();
+            assert_eq!((s.mag_x, s.mag_y, s.mag_z), (0x0201, -200, i16::MAX));
+            assert_eq!(s.current_xyz_nanotesla().1, -30_000);
+        });
+
+        let measurement = with_bus(
+            &[read(0x67, &[0x08, 0x01, 0x02, 0x38, 0xFF, 0xFF, 0x7F])],
+            |s| {
+                let measurement = s.read_measurement().unwrap();
+                // the cache is left alone
+                assert_eq!((s.mag_x, s.mag_y, s.mag_z), (0, 0, 0));
+                measurement
+            },
+        );
+        assert_eq!(measurement.raw, (0x0201, -200, i16::MAX));
+        assert!(measurement.is_new());
+    }
+
+    #[test]
+    fn bus_temperature() {
+        let millicelsius = with_bus(&[read(0x6E, &[0x10, 0x00])], |s| {
+            s.read_temperature_millicelsius().unwrap()
+        });
+        assert_eq!(millicelsius, 27_000);
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn bus_temperature_float() {
+        let celsius = with_bus(&[read(0x6E, &[0xF8, 0xFF])], |s| {
+            s.read_temperature().unwrap()
+        });
+        assert_eq!(celsius, 24.0);
+
+        let data = [0x0A, 0x00, 0x14, 0x00, 0x1E, 0x00, 0x08, 0x00];
+        with_bus(&[read(0x68, &data)], |s| {
+            assert_eq!(s.read_with_temperature().unwrap(), 26.0);
+            assert_eq!((s.mag_x, s.mag_y, s.mag_z), (10, 20, 30));
+        });
+    }
+
+    #[test]
+    fn bus_status_polling() {
+        let ready = with_bus(&[read(0x67, &[0x08])], |s| s.data_ready().unwrap());
+        assert!(ready);
+        let status = with_bus(&[read(0x67, &[0xFF])], |s| s.status().unwrap());
+        assert!(status.overrun());
+
+        // polls every DELAY_TIME µs until ZYXDA
+        let polls = [
+            read(0x67, &[0x00]),
+            read(0x67, &[0x07]),
+            read(0x67, &[0x08]),
+        ];
+        with_bus(&polls, |s| s.wait_for_data(1000).unwrap());
+
+        let result = with_bus(&[read(0x67, &[0x00]), read(0x67, &[0x00])], |s| {
+            s.wait_for_data(DELAY_TIME)
+        });
+        assert!(matches!(result, Err(Error::Timeout)));
+
+        let sample = [0x01, 0x00, 0x02, 0x00, 0x03, 0x00];
+        with_bus(
+            &[
+                read(0x67, &[0x00]),
+                read(0x67, &[0x88]),
+                read(0x68, &sample),
+            ],
+            |s| {
+                assert!(s.read_when_ready(1000).unwrap().zyxor());
+                assert_eq!((s.mag_x, s.mag_y, s.mag_z), (1, 2, 3));
+            },
+        );
+    }
+
+    #[test]
+    fn bus_measure_once() {
+        let sample = [0x01, 0x00, 0x02, 0x00, 0x03, 0x00];
+        with_bus(
+            &[
+                write(&[0x60, 0x03]),
+                read(0x68, &[0; 6]),
+                write(&[0x60, 0x01]),
+                read(0x67, &[0x08]),
+                read(0x68, &sample),
+            ],
+            |s| {
+                s.measure_once().unwrap();
+                assert_eq!((s.mag_x, s.mag_y, s.mag_z), (1, 2, 3));
+            },
+        );
+    }
+
+    #[test]
+    fn bus_interrupts() {
+        with_bus(&[write(&[0x65, 0x34, 0x12])], |s| {
+            s.set_interrupt_threshold_raw(0x1234).unwrap()
+        });
+        with_bus(&[write(&[0x65, 0xFF, 0x7F])], |s| {
+            s.set_interrupt_threshold_raw(u16::MAX).unwrap()
+        });
+        with_bus(&[write(&[0x65, 0x0A, 0x00])], |s| {
+            s.set_interrupt_threshold_nanotesla(1_480).unwrap()
+        });
+        #[cfg(feature = "float")]
+        with_bus(&[write(&[0x65, 0x64, 0x00])], |s| {
+            s.set_interrupt_threshold(15.0).unwrap()
+        });
+        let threshold = with_bus(&[read(0x65, &[0x34, 0x12])], |s| {
+            s.interrupt_threshold_raw().unwrap()
+        });
+        assert_eq!(threshold, 0x1234);
+
+        let config = InterruptConfig::new()
+            .with_axes(true, false, true)
+            .with_latched(true);
+        with_bus(&[write(&[0x63, 0xA3])], |s| {
+            s.configure_interrupt(config).unwrap()
+        });
+        let read_back = with_bus(&[read(0x63, &[0xA3])], |s| s.interrupt_config().unwrap());
+        assert_eq!(read_back, config);
+
+        with_bus(&[read(0x62, &[0x51]), write(&[0x62, 0x11])], |s| {
+            s.route_interrupt_to_pin(false).unwrap()
+        });
+
+        let source = with_bus(&[read(0x64, &[0x91])], |s| s.interrupt_source().unwrap());
+        assert_eq!(source.positive, [true, false, false]);
+        assert_eq!(source.negative, [true, false, false]);
+        assert!(source.triggered && !source.overflow);
+    }
+
+    // The mocks never return Pending, so polling once runs a future to the end
+    #[cfg(feature = "async")]
+    fn block_on<F: core::future::Future>(future: F) -> F::Output {
+        use core::task::{Context, Poll, Waker};
+
+        let mut future = core::pin::pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future did not complete"),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn bus_async() {
+        let mut i2c = I2cMock::new(&start_sequence());
+        let mut sensor = Lis2mdlAsync::new(i2c.clone(), Address::default(), NoopDelay);
+        block_on(sensor.start()).unwrap();
+        i2c.done();
+
+        let mut i2c = I2cMock::new(&[read(0x4F, &[0x3D])].concat());
+        let mut sensor = Lis2mdlAsync::new(i2c.clone(), Address::default(), NoopDelay);
+        let result = block_on(sensor.start());
+        assert!(matches!(result, Err(Error::WrongChipId(0x3D))));
+        i2c.done();
+
+        let data = [0x01, 0x02, 0x38, 0xFF, 0xFF, 0x7F];
+        let mut i2c = I2cMock::new(&read(0x68, &data));
+        let mut sensor = Lis2mdlAsync::new(i2c.clone(), Address::default(), NoopDelay);
+        block_on(sensor.read()).unwrap();
+        assert_eq!(
+            (sensor.mag_x, sensor.mag_y, sensor.mag_z),
+            (0x0201, -200, i16::MAX)
+        );
+        // the mounting orientation applies as in the blocking driver
+        sensor.set_orientation(Orientation::ROTATE_Z_90);
+        assert_eq!(sensor.current_xyz_nanotesla(), (30_000, 76_950, 4_915_050));
+        i2c.done();
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn bus_async_wait_for_data_ready() {
+        use embedded_hal_mock::eh1::digital::{
+            Mock as PinMock, State, Transaction as PinTransaction,
+        };
+
+        let sample = [0x01, 0x00, 0x02, 0x00, 0x03, 0x00];
+        let mut i2c = I2cMock::new(&read(0x68, &sample));
+        let mut pin = PinMock::new(&[PinTransaction::wait_for_state(State::High)]);
+        let mut sensor = Lis2mdlAsync::new(i2c.clone(), Address::default(), NoopDelay);
+        block_on(sensor.wait_for_data_ready(&mut pin)).unwrap();
+        assert_eq!((sensor.mag_x, sensor.mag_y, sensor.mag_z), (1, 2, 3));
+        i2c.done();
+        pin.done();
+    }
+
+    #[test]
+    fn bus_hard_iron_offset() {
+        with_bus(&[write(&[0x45, 0x01, 0x00, 0xFE, 0xFF, 0x34, 0x12])], |s| {
+            s.set_hard_iron_offset_raw([1, -2, 0x1234]).unwrap()
+        });
+        let offset = with_bus(&[read(0x45, &[0x01, 0x00, 0xFE, 0xFF, 0x34, 0x12])], |s| {
+            s.hard_iron_offset_raw().unwrap()
+        });
+        assert_eq!(offset, [1, -2, 0x1234]);
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn bus_store_hard_iron_offset() {
+        let expected = [
+            // 15 µT = 100 LSB on X, -0.15 µT = -1 LSB on Y
+            write(&[0x45, 0x64, 0x00, 0xFF, 0xFF, 0x00, 0x00]),
+            read(0x61, &[0x01]),
+            write(&[0x61, 0x09]),
+            // a new calibration replaces it, absolute rather than added
+            write(&[0x45, 0x0A, 0x00, 0x00, 0x00, 0x02, 0x00]),
+            read(0x61, &[0x09]),
+            write(&[0x61, 0x09]),
+        ];
+        with_bus(&expected, |s| {
+            s.set_calibration(Calibration::new().with_hard_iron([15.0, -0.15, 0.0]));
+            s.store_hard_iron_offset().unwrap();
+            // the calibration keeps the offset, but no longer applies it
+            assert_eq!(s.calibration().hard_iron, [15.0, -0.15, 0.0]);
+            assert_eq!(s.calibration().device_offset(), [15.0, -0.15, 0.0]);
+            assert_eq!(s.calibrated_xyz(), (0.0, 0.0, 0.0));
+            assert!(s.config().interrupt_on_corrected_data);
+
+            // already in the registers
+            s.store_hard_iron_offset().unwrap();
+
+            s.set_calibration(Calibration::new().with_hard_iron([1.5, 0.0, 0.3]));
+            s.store_hard_iron_offset().unwrap();
+        });
+
+        // no offset, so the registers are left alone
+        with_bus(&[], |s| s.store_hard_iron_offset().unwrap());
+    }
+
+    #[test]
+    fn bus_self_test() {
+        let samples = |xyz: [i16; 3]| {
+            let mut data = [0u8; 6];
+            for (chunk, value) in data.chunks_exact_mut(2).zip(xyz) {
+                chunk.copy_from_slice(&value.to_le_bytes());
+            }
+            // one discarded sample, then the averaged ones
+            (0..=50)
+                .flat_map(|_| [read(0x67, &[0x08]), read(0x68, &data)].concat())
+                .collect::<Vec<_>>()
+        };
+        let expected = [
+            read(0x60, &[0x00]),
+            read(0x61, &[0x00]),
+            read(0x62, &[0x11]),
+            write(&[0x60, 0x8C]),
+            write(&[0x61, 0x02]),
+            write(&[0x62, 0x10]),
+            samples([10, 20, 30]),
+            write(&[0x62, 0x12]),
+            samples([110, 20, 230]),
+            write(&[0x62, 0x11]),
+            write(&[0x61, 0x00]),
+            write(&[0x60, 0x00]),
+        ];
+        let report = with_bus(&expected, |s| s.self_test().unwrap());
+        assert_eq!(report.delta, [100, 0, 200]);
+        assert_eq!(report.axis_passed, [true, false, true]);
+    }
+
+    #[test]
+    fn bus_write_errors() {
+        let result = with_failing_bus(&[], |s| s.start());
+        assert!(matches!(result, Err(Error::I2C(ErrorKind::Other))));
+
+        let result = with_failing_bus(&[], |s| s.init());
+        assert!(matches!(result, Err(Error::I2C(ErrorKind::Other))));
+
+        let result = with_failing_bus(&[], |s| s.configure(Config::new()));
+        assert!(matches!(result, Err(Error::I2C(ErrorKind::Other))));
+        // a failed configure does not record the new config
+        let config = Config::new().with_odr(OutputDataRate::Hz100);
+        with_failing_bus(&[write(&[0x61, 0x00])], |s| {
+            assert!(s.configure(config).is_err());
+            assert_eq!(s.config(), Config::default());
+        });
+
+        let result = with_failing_bus(&[], |s| s.set_interrupt_threshold_raw(1));
+        assert!(matches!(result, Err(Error::I2C(ErrorKind::Other))));
+    }
+
+    #[test]
+    fn bus_long_write() {
+        // writes are not limited to the 6-byte register blocks
+        let data: Vec<u8> = (0x60..0x70).collect();
+        with_bus(&[write(&data)], |s| {
+            Interface::write_registers(&mut s.iface, 0x60, &data[1..]).unwrap()
+        });
+    }
+
+    #[test]
+    fn bus_read_errors() {
+        let mut sensor = Lis2mdl::new(FailingI2c, Address::default(), NoopDelay);
+
+        assert!(matches!(sensor.whoami(), Err(Error::I2C(ErrorKind::Bus))));
+        assert!(matches!(sensor.read(), Err(Error::I2C(ErrorKind::Bus))));
+        assert!(matches!(
+            sensor.read_measurement(),
+            Err(Error::I2C(ErrorKind::Bus))
+        ));
+        assert!(matches!(
+            sensor.wait_for_data(1000),
+            Err(Error::I2C(ErrorKind::Bus))
+        ));
+        assert!(matches!(
+            sensor.self_test(),
+            Err(Error::I2C(ErrorKind::Bus))
+        ));
+        assert!(matches!(
+            sensor.set_register(0x60, 0),
+            Err(Error::I2C(ErrorKind::Bus))
+        ));
+    }
+
+    // Simulator-backed tests
+
+    fn simulated(
+        field: sim::Field,
+    ) -> (
+        sim::Simulator,
+        Lis2mdl<I2cInterface<sim::Simulator>, sim::SimDelay>,
+    ) {
+        let simulator = sim::Simulator::new();
+        simulator.set_field(field);
+        let mut sensor = Lis2mdl::new(simulator.clone(), Address::default(), simulator.delay());
+        sensor.init().unwrap();
+        (simulator, sensor)
+    }
+
+    #[test]
+    fn sim_read() {
+        let (simulator, mut sensor) = simulated(sim::Field::Constant([15.0, -30.0, 45.0]));
+        simulator.set_temperature(30.0);
+
+        sensor.read_when_ready(200_000).unwrap();
+        assert_eq!((sensor.mag_x, sensor.mag_y, sensor.mag_z), (100, -200, 300));
+        assert_eq!(sensor.read_temperature_millicelsius().unwrap(), 30_000);
+        // reading the output cleared ZYXDA
+        assert!(!sensor.data_ready().unwrap());
+
+        simulator.set_noise(0.3, 7);
+        for _ in 0..20 {
+            sensor.read_when_ready(200_000).unwrap();
+            assert!((sensor.mag_x - 100).abs() <= 2 && (sensor.mag_z - 300).abs() <= 2);
+        }
+    }
+
+    #[test]
+    fn sim_timing_and_overrun() {
+        let (simulator, mut sensor) = simulated(sim::Field::Constant([15.0, 0.0, 0.0]));
+
+        // 10 Hz: nothing until 100 ms after configure
+        simulator.advance_ns(99_000_000);
+        assert!(!sensor.read_measurement().unwrap().is_new());
+        simulator.advance_ns(1_000_000);
+        let measurement = sensor.read_measurement().unwrap();
+        assert!(measurement.is_new() && !measurement.overrun());
+        assert_eq!(measurement.raw, (100, 0, 0));
+
+        simulator.advance_ns(250_000_000);
+        assert!(sensor.status().unwrap().overrun());
+        assert!(sensor.read_measurement().unwrap().overrun());
+        assert_eq!(sensor.status().unwrap().bits(), 0);
+
+        assert!(matches!(sensor.wait_for_data(40_000), Err(Error::Timeout)));
+    }
+
+    #[test]
+    fn sim_block_data_update() {
+        let (simulator, mut sensor) = simulated(sim::Field::Constant([15.0, 0.0, 0.0]));
+        sensor.wait_for_data(200_000).unwrap();
+
+        // X = 100 LSB, then -100 LSB (0xFF9C) arrives between the two halves
+        assert_eq!(sensor.get_register(0x68).unwrap(), 0x64);
+        simulator.set_field(sim::Field::Constant([-15.0, 0.0, 0.0]));
+        simulator.advance_ns(100_000_000);
+        assert_eq!(sensor.get_register(0x69).unwrap(), 0x00);
+
+        // the held sample is released once the high byte is read
+        assert_eq!(sensor.read_reg16(Register::OutxL).unwrap(), -100);
+    }
+
+    #[test]
+    fn sim_single_shot_and_reset() {
+        let config = Config::new()
+            .with_odr(OutputDataRate::Hz50)
+            .with_mode(Mode::Idle);
+        let simulator = sim::Simulator::new();
+        simulator.set_field(sim::Field::Constant([0.0, 0.0, -45.0]));
+        let mut sensor = Lis2mdl::new(simulator.clone(), Address::default(), simulator.delay())
+            .with_config(config);
+        sensor.init().unwrap();
+
+        simulator.advance_ns(1_000_000_000);
+        assert!(!sensor.data_ready().unwrap());
+
+        sensor.measure_once().unwrap();
+        assert_eq!(sensor.mag_z, -300);
+        assert_eq!(sensor.read_reg::<CfgRegA>().unwrap().mode(), Mode::Idle);
+        simulator.advance_ns(1_000_000_000);
+        assert!(!sensor.data_ready().unwrap());
+
+        sensor.set_hard_iron_offset_raw([1, 2, 3]).unwrap();
+        sensor.reset().unwrap();
+        assert_eq!(sensor.hard_iron_offset_raw().unwrap(), [0, 0, 0]);
+        assert_eq!(simulator.peek(0x60), 0x03);
+    }
+
+    #[test]
+    fn sim_single_shot_after_continuous() {
+        let (simulator, mut sensor) = simulated(sim::Field::Constant([15.0, 0.0, 0.0]));
+        // an unread continuous sample is waiting
+        sensor.wait_for_data(200_000).unwrap();
+
+        simulator.set_field(sim::Field::Constant([-15.0, 0.0, 0.0]));
+        let before = simulator.now_ns();
+        sensor.measure_once().unwrap();
+        assert_eq!(sensor.mag_x, -100);
+        assert!(simulator.now_ns() > before);
+    }
+
+    #[test]
+    fn sim_offsets_and_interrupts() {
+        let (simulator, mut sensor) = simulated(sim::Field::Constant([30.0, -30.0, 0.0]));
+        sensor.set_interrupt_threshold_raw(150).unwrap();
+        sensor
+            .configure_interrupt(
+                InterruptConfig::new()
+                    .with_axes(true, true, true)
+                    .with_latched(true),
+            )
+            .unwrap();
+
+        sensor.read_when_ready(200_000).unwrap();
+        let source = sensor.interrupt_source().unwrap();
+        assert!(source.triggered);
+        assert_eq!(source.positive, [true, false, false]);
+        assert_eq!(source.negative, [false, true, false]);
+        // latched events clear on read
+        assert!(!sensor.interrupt_source().unwrap().triggered);
+
+        // the OFFSET registers are subtracted from the output, and with
+        // INT_on_DataOFF the threshold sees the corrected data
+        sensor.set_hard_iron_offset_raw([200, -200, 0]).unwrap();
+        sensor
+            .modify_reg::<CfgRegB, _>(|reg| reg.with_int_on_data_off(true))
+            .unwrap();
+        simulator.advance_ns(100_000_000);
+        sensor.read().unwrap();
+        assert_eq!((sensor.mag_x, sensor.mag_y), (0, 0));
+        assert!(!sensor.interrupt_source().unwrap().triggered);
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn sim_stored_hard_iron_offset() {
+        let field = [15.0, -30.0, 45.0];
+        let (simulator, mut sensor) = simulated(sim::Field::Constant(field));
+
+        // storing while collecting restarts min/max on the corrected output
+        sensor.begin_calibration();
+        sensor.read_when_ready(200_000).unwrap();
+        sensor.store_hard_iron_offset().unwrap();
+        assert_eq!(sensor.hard_iron_offset_raw().unwrap(), [100, -200, 300]);
+        sensor.read_when_ready(200_000).unwrap();
+        assert_eq!((sensor.mag_x, sensor.mag_y, sensor.mag_z), (0, 0, 0));
+        sensor.finish_calibration();
+        assert_eq!(sensor.calibration().hard_iron, field);
+
+        // the saved calibration still holds the offset after a reset
+        let saved = Calibration::from_bytes(&sensor.calibration().to_bytes()).unwrap();
+        assert_eq!(saved.hard_iron, field);
+        sensor.init().unwrap();
+        assert_eq!(sensor.hard_iron_offset_raw().unwrap(), [0, 0, 0]);
+        sensor.set_calibration(saved);
+        sensor.read_when_ready(200_000).unwrap();
+        assert_eq!(sensor.calibrated_xyz(), (0.0, 0.0, 0.0));
+
+        // storing it again writes the same registers, not twice the offset
+        sensor.store_hard_iron_offset().unwrap();
+        assert_eq!(sensor.hard_iron_offset_raw().unwrap(), [100, -200, 300]);
+        simulator.advance_ns(100_000_000);
+        sensor.read().unwrap();
+        assert_eq!((sensor.mag_x, sensor.mag_y, sensor.mag_z), (0, 0, 0));
+        assert_eq!(sensor.calibrated_xyz(), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn sim_self_test() {
+        let (_simulator, mut sensor) = simulated(sim::Field::Constant([20.0, 0.0, -40.0]));
+        #[cfg(feature = "float")]
+        sensor.begin_calibration();
+        let report = sensor.self_test().unwrap();
+        assert!(report.passed());
+        assert_eq!(report.delta, sim::SELF_TEST_DELTA.map(|d| d as i32));
+        assert_eq!(sensor.read_config().unwrap(), Config::default());
+
+        // self-test samples stay out of the cache and the calibration
+        assert_eq!((sensor.mag_x, sensor.mag_y, sensor.mag_z), (0, 0, 0));
+        #[cfg(feature = "float")]
+        assert_eq!(sensor.calibration().hard_iron, [0.0; 3]);
+    }
+
+    #[test]
+    fn sim_spi() {
+        let simulator = sim::Simulator::new();
+        simulator.set_field(sim::Field::Constant([15.0, -30.0, 45.0]));
+        let mut sensor = Lis2mdl::new_spi(simulator.clone(), SpiWires::Four, simulator.delay());
+        sensor.init().unwrap();
+        assert_eq!(simulator.peek(0x62), 0x35);
+
+        sensor.read_when_ready(200_000).unwrap();
+        assert_eq!((sensor.mag_x, sensor.mag_y, sensor.mag_z), (100, -200, 300));
+
+        // I2C_DIS is set, so the device no longer answers on I²C
+        let mut i2c = Lis2mdl::new(simulator.clone(), Address::default(), simulator.delay());
+        assert!(matches!(i2c.whoami(), Err(Error::I2C(_))));
+    }
+
+    #[test]
+    fn sim_rotating_heading() {
+        let field = sim::Field::Rotating {
+            horizontal: 30.0,
+            vertical: -40.0,
+            period_ms: 1000,
+        };
+        let (_simulator, mut sensor) = simulated(field);
+
+        // 10 Hz samples of a field turning once a second: 36° apart
+        let mut previous: Option<i32> = None;
+        for _ in 0..10 {
+            sensor.read_when_ready(200_000).unwrap();
+            let heading = sensor.heading_centidegrees() as i32;
+            if let Some(previous) = previous {
+                let step = (heading - previous).rem_euclid(36_000);
+                assert!((step - 3600).abs() <= 50, "{step}");
+            }
+            previous = Some(heading);
+        }
+    }
+
+    #[test]
+    fn sim_pin() {
+        let (simulator, sensor) = simulated(sim::Field::Constant([30.0, 0.0, -45.0]));
+        let mut sensor = WithPin::new(sensor, simulator.pin());
+
+        let measurement = sensor.wait_for_data_ready(200_000).unwrap();
+        assert_eq!(measurement.raw, (200, 0, -300));
+        assert_eq!(simulator.peek(0x62) & 0x41, 0x01);
+        // reading the sample drops DRDY
+        assert!(simulator.pin().is_low().unwrap());
+
+        let config = InterruptConfig::new().with_axes(true, false, true);
+        let driver = sensor.driver_mut();
+        driver.set_interrupt_threshold_raw(250).unwrap();
+        driver.configure_interrupt(config).unwrap();
+        let source = sensor.wait_for_threshold_event(200_000).unwrap();
+        assert_eq!(sensor.routing(), Some(PinFunction::Threshold));
+        assert_eq!(simulator.peek(0x62) & 0x41, 0x40);
+        assert_eq!(source.negative, [false, false, true]);
+        assert_eq!(source.positive, [false; 3]);
+
+        // X at 200 LSB never crosses 250
+        let config = config.with_axes(true, false, false);
+        sensor.driver_mut().configure_interrupt(config).unwrap();
+        simulator.advance_ns(100_000_000);
+        let result = sensor.wait_for_threshold_event(300_000);
+        assert!(matches!(result, Err(Error::Timeout)));
+
+        // polling without a pin gives the same results
+        let (sensor, _) = sensor.release();
+        let mut sensor = WithPin::polling(sensor);
+        sensor
+            .driver_mut()
+            .configure_interrupt(config.with_axes(false, false, true))
+            .unwrap();
+        assert!(sensor.wait_for_threshold_event(200_000).unwrap().triggered);
+        assert_eq!(
+            sensor.wait_for_data_ready(200_000).unwrap().raw,
+            (200, 0, -300)
+        );
+    }
+
+    #[test]
+    fn sim_sensor_offset() {
+        let (simulator, mut sensor) = simulated(sim::Field::Constant([20.0, 0.0, -40.0]));
+        simulator.set_sensor_offset([50, -30, 10]);
+        simulator.set_noise(0.3, 11);
+        sensor.set_hard_iron_offset_raw([5, 5, 5]).unwrap();
+        // register bits outside the cached config, as measure_once leaves them
+        sensor
+            .modify_reg::<CfgRegA, _>(|reg| reg.with_mode(Mode::Idle))
+            .unwrap();
+        sensor
+            .modify_reg::<CfgRegB, _>(|reg| reg.with_lpf(true))
+            .unwrap();
+
+        let estimate = sensor.estimate_sensor_offset(32).unwrap();
+        for (estimate, actual) in estimate.iter().zip([50, -30, 10]) {
+            assert!((estimate - actual).abs() <= 1, "{estimate}");
+        }
+        assert_eq!(simulator.peek(0x60), 0x03);
+        assert_eq!(simulator.peek(0x61), 0x01);
+        assert_eq!(sensor.config(), Config::default());
+
+        // in the OFFSET registers it leaves only the field
+        sensor.configure(Config::default()).unwrap();
+        sensor.apply_sensor_offset(estimate).unwrap();
+        assert_eq!(sensor.hard_iron_offset_raw().unwrap(), estimate);
+        simulator.set_noise(0.0, 0);
+        sensor.read_when_ready(200_000).unwrap();
+        assert!((sensor.mag_x - 133).abs() <= 1);
+        assert!(sensor.mag_y.abs() <= 1);
+        assert!((sensor.mag_z + 267).abs() <= 1);
+
+        // a stored hard-iron offset is written alongside it
+        #[cfg(feature = "float")]
+        {
+            sensor.set_calibration(Calibration::new().with_hard_iron([20.0, 0.0, -40.0]));
+            sensor.store_hard_iron_offset().unwrap();
+            let stored = sensor.hard_iron_offset_raw().unwrap();
+            assert_eq!(stored, [133 + estimate[0], estimate[1], -267 + estimate[2]]);
+        }
+    }
 }
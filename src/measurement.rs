@@ -0,0 +1,74 @@
+use embedded_hal::delay::DelayNs;
+
+use crate::fixed::raw_to_nanotesla;
+use crate::register::{Register, StatusReg};
+use crate::{Error, Interface, Lis2mdl, decode_xyz};
+
+/// One sample together with the status flags read in the same burst.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Measurement {
+    /// Output in LSB (1.5 mG), in the device frame.
+    pub raw: (i16, i16, i16),
+    /// STATUS_REG as read just before the output registers.
+    pub status: StatusReg,
+}
+
+impl Measurement {
+    /// Decode a STATUS_REG..OUTZ_H_REG burst. Raw values are in chip axes.
+    pub fn from_bytes(bytes: &[u8; 7]) -> Self {
+        Measurement {
+            raw: decode_xyz(&bytes[1..]),
+            status: StatusReg::from_bits(bytes[0]),
+        }
+    }
+
+    /// Whether this is a new sample (ZYXDA) rather than a repeat of the last.
+    pub const fn is_new(&self) -> bool {
+        self.status.zyxda()
+    }
+
+    /// Whether a sample was overwritten before this one was read.
+    pub const fn overrun(&self) -> bool {
+        self.status.overrun()
+    }
+
+    pub const fn nanotesla(&self) -> (i32, i32, i32) {
+        (
+            raw_to_nanotesla(self.raw.0),
+            raw_to_nanotesla(self.raw.1),
+            raw_to_nanotesla(self.raw.2),
+        )
+    }
+
+    #[cfg(feature = "float")]
+    pub fn microtesla(&self) -> (f32, f32, f32) {
+        self.field().microtesla()
+    }
+
+    #[cfg(feature = "float")]
+    pub fn field(&self) -> crate::MagneticField {
+        crate::MagneticField::from_raw(self.raw)
+    }
+}
+
+impl<IFACE, DELAY, E> Lis2mdl<IFACE, DELAY>
+where
+    DELAY: DelayNs,
+    IFACE: Interface<Error = E>,
+{
+    /// Read the status and output registers in one burst and return them
+    /// with the mounting orientation applied.
+    ///
+    /// Unlike `read`, this leaves `mag_x/y/z` and the calibration untouched.
+    /// The status flags tell whether the sample is new, so no separate
+    /// `data_ready` poll is needed.
+    pub fn read_measurement(&mut self) -> Result<Measurement, Error<E>> {
+        let mut buffer = [0u8; 7];
+        self.read_registers(Register::StatusReg.addr(), &mut buffer)?;
+
+        let mut measurement = Measurement::from_bytes(&buffer);
+        measurement.raw = self.orientation.apply_raw(measurement.raw);
+
+        Ok(measurement)
+    }
+}
@@ -0,0 +1,191 @@
+use embedded_hal::delay::DelayNs;
+
+#[cfg(feature = "float")]
+use crate::CalibrationState;
+use crate::register::{CfgRegA, CfgRegB, Mode, OutputDataRate, Register};
+use crate::{Config, Error, Interface, Lis2mdl};
+#[cfg(feature = "float")]
+use crate::{LIS2MDL_MAG_LSB, LIS2MDL_MILLIGAUSS_TO_MICROTESLA};
+
+// OFF_CANC pairs a set and a reset sample, so let a full pair pass after
+// switching it
+const SETTLE_SAMPLES: u32 = 2;
+
+#[cfg(feature = "float")]
+const MICROTESLA_PER_LSB: f32 = LIS2MDL_MAG_LSB * LIS2MDL_MILLIGAUSS_TO_MICROTESLA;
+
+#[cfg(feature = "float")]
+fn microtesla_to_raw(microtesla: f32) -> i16 {
+    // float to int casts saturate
+    let lsb = microtesla / MICROTESLA_PER_LSB;
+    if lsb < 0.0 {
+        (lsb - 0.5) as i16
+    } else {
+        (lsb + 0.5) as i16
+    }
+}
+
+impl<IFACE, DELAY, E> Lis2mdl<IFACE, DELAY>
+where
+    DELAY: DelayNs,
+    IFACE: Interface<Error = E>,
+{
+    /// Write OFFSET_X/Y/Z_REG in LSB. The device subtracts these from every
+    /// output sample. Offsets are in chip axes, not the mounting orientation.
+    pub fn set_hard_iron_offset_raw(&mut self, offset: [i16; 3]) -> Result<(), Error<E>> {
+        let mut buffer = [0u8; 6];
+        for (chunk, value) in buffer.chunks_exact_mut(2).zip(offset) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        self.iface
+            .write_registers(Register::OffsetXRegL.addr(), &buffer)
+    }
+
+    // The OFFSET registers hold the stored hard-iron offset plus the
+    // applied sensor offset, both in chip axes
+    fn write_offset_registers(
+        &mut self,
+        hard_iron: [i16; 3],
+        sensor: [i16; 3],
+    ) -> Result<(), Error<E>> {
+        self.set_hard_iron_offset_raw(
+            [0, 1, 2].map(|axis| hard_iron[axis].saturating_add(sensor[axis])),
+        )
+    }
+
+    // The calibration's hard-iron offset currently in the OFFSET registers
+    #[cfg(feature = "float")]
+    fn device_hard_iron_raw(&self) -> [i16; 3] {
+        let [x, y, z] = self.calibration.device_offset();
+        let (x, y, z) = self.orientation.inverse().apply((x, y, z));
+        [x, y, z].map(microtesla_to_raw)
+    }
+
+    #[cfg(not(feature = "float"))]
+    fn device_hard_iron_raw(&self) -> [i16; 3] {
+        [0; 3]
+    }
+
+    pub fn hard_iron_offset_raw(&mut self) -> Result<[i16; 3], Error<E>> {
+        let mut buffer = [0u8; 6];
+        self.read_registers(Register::OffsetXRegL.addr(), &mut buffer)?;
+
+        let (x, y, z) = crate::decode_xyz(&buffer);
+        Ok([x, y, z])
+    }
+
+    #[cfg(feature = "float")]
+    /// Write the hardware offsets in µT, rounded to the nearest LSB.
+    pub fn set_hard_iron_offset(&mut self, offset: (f32, f32, f32)) -> Result<(), Error<E>> {
+        self.set_hard_iron_offset_raw([
+            microtesla_to_raw(offset.0),
+            microtesla_to_raw(offset.1),
+            microtesla_to_raw(offset.2),
+        ])
+    }
+
+    #[cfg(feature = "float")]
+    pub fn hard_iron_offset(&mut self) -> Result<(f32, f32, f32), Error<E>> {
+        let [x, y, z] = self.hard_iron_offset_raw()?;
+
+        Ok((
+            crate::raw_to_microtesla(x),
+            crate::raw_to_microtesla(y),
+            crate::raw_to_microtesla(z),
+        ))
+    }
+
+    #[cfg(feature = "float")]
+    /// Write the calibration's hard-iron offsets to the OFFSET registers
+    /// and check threshold interrupts against the corrected data
+    /// (`Config::interrupt_on_corrected_data`, kept in the stored config).
+    ///
+    /// The registers get the absolute offset, together with any offset from
+    /// `apply_sensor_offset`, so storing again, e.g. after
+    /// loading a saved calibration on the next boot, is safe. The calibration
+    /// keeps the full offset and only applies what the device does not
+    /// subtract; any soft-iron matrix still applies. A calibration still
+    /// collecting restarts its min/max, since new samples are corrected.
+    pub fn store_hard_iron_offset(&mut self) -> Result<(), Error<E>> {
+        let hard_iron = self.calibration.hard_iron;
+        if hard_iron == self.calibration.device_offset() {
+            // the registers already hold it
+            return Ok(());
+        }
+
+        let [x, y, z] = hard_iron;
+        // calibration works in the device frame, the registers in chip axes
+        let (x, y, z) = self.orientation.inverse().apply((x, y, z));
+        self.write_offset_registers([x, y, z].map(microtesla_to_raw), self.sensor_offset)?;
+        // read-modify-write keeps CFG_REG_B bits set outside `Config`
+        self.modify_reg::<CfgRegB, _>(|reg| reg.with_int_on_data_off(true))?;
+        self.config = self.config.with_interrupt_on_corrected_data(true);
+
+        self.calibration.set_device_offset(hard_iron);
+        if self.calibration.state() == CalibrationState::Collecting {
+            self.calibration.begin();
+        }
+
+        Ok(())
+    }
+
+    /// Estimate the sensor's own offset from its set/reset behaviour.
+    ///
+    /// Averages `samples` readings at 100 Hz with offset cancellation off,
+    /// where the output is field plus offset, and `samples` with it on,
+    /// where alternating set and reset pulses cancel the offset. The
+    /// difference is the offset, in LSB and chip axes. Keep the device still
+    /// meanwhile; CFG_REG_A and CFG_REG_B are restored afterwards as read
+    /// from the device.
+    ///
+    /// This is the offset a strong magnet can leave behind, which a min/max
+    /// or ellipsoid calibration would otherwise mistake for hard iron. When
+    /// running without offset cancellation, pass it to `apply_sensor_offset`
+    /// so the device subtracts it.
+    pub fn estimate_sensor_offset(&mut self, samples: u16) -> Result<[i16; 3], Error<E>> {
+        let saved = self.config;
+        let saved_a = self.read_reg::<CfgRegA>()?;
+        let saved_b = self.read_reg::<CfgRegB>()?;
+        let samples = samples.max(1) as u32;
+        let base = Config::from_registers(saved_a, saved_b)
+            .with_odr(OutputDataRate::Hz100)
+            .with_mode(Mode::Continuous);
+
+        let result = self
+            .configure(base.with_offset_cancellation(false))
+            .and_then(|_| self.sum_raw(SETTLE_SAMPLES, samples))
+            .and_then(|without| {
+                self.configure(base.with_offset_cancellation(true))?;
+                let with = self.sum_raw(SETTLE_SAMPLES, samples)?;
+
+                let n = samples as i64;
+                Ok([0, 1, 2].map(|axis| {
+                    // rounded to the nearest LSB
+                    let difference = without[axis] - with[axis];
+                    let offset = (difference + difference.signum() * n / 2) / n;
+                    offset.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+                }))
+            });
+
+        // put the registers back whatever the outcome, B before A as in
+        // `configure`
+        self.write_reg(saved_b)?;
+        self.write_reg(saved_a)?;
+        self.config = saved;
+
+        result
+    }
+
+    /// Have the device subtract an offset from `estimate_sensor_offset`.
+    ///
+    /// Replaces any previously applied sensor offset; the OFFSET registers
+    /// hold it together with the hard-iron offset from
+    /// `store_hard_iron_offset`. A soft reset clears both.
+    pub fn apply_sensor_offset(&mut self, offset: [i16; 3]) -> Result<(), Error<E>> {
+        let hard_iron = self.device_hard_iron_raw();
+        self.write_offset_registers(hard_iron, offset)?;
+        self.sensor_offset = offset;
+
+        Ok(())
+    }
+}
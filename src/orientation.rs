@@ -0,0 +1,181 @@
+// Mounting orientation: maps the chip's X/Y/Z axes onto the device frame
+
+/// A signed chip axis.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    PlusX,
+    MinusX,
+    PlusY,
+    MinusY,
+    PlusZ,
+    MinusZ,
+}
+
+impl Direction {
+    const ALL: [Direction; 6] = [
+        Direction::PlusX,
+        Direction::MinusX,
+        Direction::PlusY,
+        Direction::MinusY,
+        Direction::PlusZ,
+        Direction::MinusZ,
+    ];
+
+    const fn index(self) -> usize {
+        match self {
+            Direction::PlusX | Direction::MinusX => 0,
+            Direction::PlusY | Direction::MinusY => 1,
+            Direction::PlusZ | Direction::MinusZ => 2,
+        }
+    }
+
+    const fn sign(self) -> i8 {
+        match self {
+            Direction::PlusX | Direction::PlusY | Direction::PlusZ => 1,
+            _ => -1,
+        }
+    }
+
+    const fn from_row(row: [i8; 3]) -> Option<Self> {
+        match row {
+            [1, 0, 0] => Some(Direction::PlusX),
+            [-1, 0, 0] => Some(Direction::MinusX),
+            [0, 1, 0] => Some(Direction::PlusY),
+            [0, -1, 0] => Some(Direction::MinusY),
+            [0, 0, 1] => Some(Direction::PlusZ),
+            [0, 0, -1] => Some(Direction::MinusZ),
+            _ => None,
+        }
+    }
+}
+
+/// Which chip axis points along each device axis.
+///
+/// Only proper rotations are accepted, so there are exactly 24; see
+/// [`Orientation::all`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Orientation {
+    axes: [Direction; 3],
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation::IDENTITY
+    }
+}
+
+impl Orientation {
+    /// Chip axes match the device axes.
+    pub const IDENTITY: Orientation = Orientation {
+        axes: [Direction::PlusX, Direction::PlusY, Direction::PlusZ],
+    };
+    /// Chip rotated 90° counter-clockwise on the board, seen from above.
+    pub const ROTATE_Z_90: Orientation = Orientation {
+        axes: [Direction::MinusY, Direction::PlusX, Direction::PlusZ],
+    };
+    pub const ROTATE_Z_180: Orientation = Orientation {
+        axes: [Direction::MinusX, Direction::MinusY, Direction::PlusZ],
+    };
+    pub const ROTATE_Z_270: Orientation = Orientation {
+        axes: [Direction::PlusY, Direction::MinusX, Direction::PlusZ],
+    };
+    /// Chip on the bottom side, flipped over the device X axis.
+    pub const BOTTOM: Orientation = Orientation {
+        axes: [Direction::PlusX, Direction::MinusY, Direction::MinusZ],
+    };
+
+    /// Device X, Y and Z as chip axes. Returns `None` unless the axes are
+    /// distinct and form a right-handed frame.
+    pub fn new(x: Direction, y: Direction, z: Direction) -> Option<Self> {
+        let orientation = Orientation { axes: [x, y, z] };
+        (orientation.determinant() == 1).then_some(orientation)
+    }
+
+    /// Build from a rotation matrix taking chip vectors to device vectors.
+    pub fn from_matrix(matrix: [[i8; 3]; 3]) -> Option<Self> {
+        Orientation::new(
+            Direction::from_row(matrix[0])?,
+            Direction::from_row(matrix[1])?,
+            Direction::from_row(matrix[2])?,
+        )
+    }
+
+    pub fn matrix(&self) -> [[i8; 3]; 3] {
+        self.axes.map(|direction| {
+            let mut row = [0; 3];
+            row[direction.index()] = direction.sign();
+            row
+        })
+    }
+
+    /// Every orthogonal mounting orientation.
+    pub fn all() -> [Orientation; 24] {
+        let mut all = [Orientation::IDENTITY; 24];
+        let mut count = 0;
+        for x in Direction::ALL {
+            for y in Direction::ALL {
+                for z in Direction::ALL {
+                    if let Some(orientation) = Orientation::new(x, y, z) {
+                        all[count] = orientation;
+                        count += 1;
+                    }
+                }
+            }
+        }
+        all
+    }
+
+    /// `self` followed by `next`, e.g. `Orientation::BOTTOM.then(Orientation::ROTATE_Z_90)`.
+    pub fn then(&self, next: Orientation) -> Orientation {
+        let (a, b) = (next.matrix(), self.matrix());
+        let mut product = [[0i8; 3]; 3];
+        for (i, row) in product.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j];
+            }
+        }
+        // a product of rotations is a rotation
+        Orientation::from_matrix(product).unwrap_or_default()
+    }
+
+    /// The reverse mapping, from the device frame back to chip axes.
+    pub fn inverse(&self) -> Orientation {
+        let m = self.matrix();
+        let transposed = [
+            [m[0][0], m[1][0], m[2][0]],
+            [m[0][1], m[1][1], m[2][1]],
+            [m[0][2], m[1][2], m[2][2]],
+        ];
+        Orientation::from_matrix(transposed).unwrap_or_default()
+    }
+
+    /// Map a raw chip sample into the device frame. -32768 saturates.
+    pub fn apply_raw(&self, raw: (i16, i16, i16)) -> (i16, i16, i16) {
+        let chip = [raw.0, raw.1, raw.2];
+        let [x, y, z] = self.axes.map(|direction| {
+            let value = chip[direction.index()];
+            if direction.sign() < 0 {
+                value.saturating_neg()
+            } else {
+                value
+            }
+        });
+        (x, y, z)
+    }
+
+    /// Map a chip-frame vector into the device frame.
+    pub fn apply(&self, xyz: (f32, f32, f32)) -> (f32, f32, f32) {
+        let chip = [xyz.0, xyz.1, xyz.2];
+        let [x, y, z] = self
+            .axes
+            .map(|direction| chip[direction.index()] * direction.sign() as f32);
+        (x, y, z)
+    }
+
+    fn determinant(&self) -> i32 {
+        let m = self.matrix().map(|row| row.map(|v| v as i32));
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+}
@@ -0,0 +1,272 @@
+// INT/DRDY pin support. The LIS2MDL has a single interrupt pin that carries
+// either data-ready (DRDY_on_PIN) or the threshold interrupt (INT_on_PIN);
+// `WithPin` switches the routing to whichever event is being waited for.
+
+use core::convert::Infallible;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, InputPin};
+
+use crate::register::CfgRegC;
+use crate::{DELAY_TIME, Error, Interface, InterruptSource, Lis2mdl, Measurement};
+
+/// What the INT/DRDY pin signals.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PinFunction {
+    /// New sample available, active high (DRDY_on_PIN).
+    DataReady,
+    /// Threshold interrupt, polarity set by `InterruptConfig::active_high`
+    /// (INT_on_PIN).
+    Threshold,
+}
+
+impl PinFunction {
+    const fn route(self, reg: CfgRegC) -> CfgRegC {
+        let data_ready = matches!(self, PinFunction::DataReady);
+        reg.with_drdy_on_pin(data_ready)
+            .with_int_on_pin(!data_ready)
+    }
+}
+
+/// Placeholder pin type for [`WithPin::polling`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NoPin;
+
+impl digital::ErrorType for NoPin {
+    type Error = Infallible;
+}
+
+impl InputPin for NoPin {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(false)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(true)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::digital::Wait for NoPin {
+    async fn wait_for_high(&mut self) -> Result<(), Infallible> {
+        core::future::pending().await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Infallible> {
+        core::future::pending().await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Infallible> {
+        core::future::pending().await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Infallible> {
+        core::future::pending().await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Infallible> {
+        core::future::pending().await
+    }
+}
+
+/// A driver (`Lis2mdl` or `Lis2mdlAsync`) paired with an optional INT/DRDY
+/// input. Without a pin the waits fall back to polling registers.
+///
+/// The pin routing last written is cached; call `invalidate_routing` after
+/// changing `CFG_REG_C` through `driver_mut`.
+#[derive(Debug)]
+pub struct WithPin<D, P> {
+    driver: D,
+    pin: Option<P>,
+    routing: Option<PinFunction>,
+}
+
+impl<D, P> WithPin<D, P> {
+    pub fn new(driver: D, pin: P) -> Self {
+        WithPin {
+            driver,
+            pin: Some(pin),
+            routing: None,
+        }
+    }
+
+    pub fn driver(&self) -> &D {
+        &self.driver
+    }
+
+    pub fn driver_mut(&mut self) -> &mut D {
+        &mut self.driver
+    }
+
+    /// The current pin routing, if known.
+    pub fn routing(&self) -> Option<PinFunction> {
+        self.routing
+    }
+
+    /// Rewrite the pin routing before the next wait.
+    pub fn invalidate_routing(&mut self) {
+        self.routing = None;
+    }
+
+    pub fn release(self) -> (D, Option<P>) {
+        (self.driver, self.pin)
+    }
+}
+
+impl<D> WithPin<D, NoPin> {
+    /// No pin connected: wait by polling STATUS_REG and INT_SOURCE_REG.
+    pub fn polling(driver: D) -> Self {
+        WithPin {
+            driver,
+            pin: None,
+            routing: None,
+        }
+    }
+}
+
+impl<IFACE, DELAY, P, E> WithPin<Lis2mdl<IFACE, DELAY>, P>
+where
+    DELAY: DelayNs,
+    IFACE: Interface<Error = E>,
+    P: InputPin,
+{
+    /// Route `function` to the pin (CFG_REG_C), unless it already is.
+    pub fn route(&mut self, function: PinFunction) -> Result<(), Error<E>> {
+        if self.routing != Some(function) {
+            self.driver
+                .modify_reg::<CfgRegC, _>(|reg| function.route(reg))?;
+            self.routing = Some(function);
+        }
+        Ok(())
+    }
+
+    /// Wait for a new sample and read it, or fail with `Error::Timeout`
+    /// after `timeout_us`. The pin is polled every 125 µs with the driver's
+    /// delay.
+    pub fn wait_for_data_ready(&mut self, timeout_us: u32) -> Result<Measurement, Error<E>> {
+        self.route(PinFunction::DataReady)?;
+
+        match self.pin.as_mut() {
+            Some(pin) => wait_for_level(pin, &mut self.driver.delay, true, timeout_us)?,
+            None => {
+                self.driver.wait_for_data(timeout_us)?;
+            }
+        }
+
+        self.driver.read_measurement()
+    }
+
+    /// Wait for a threshold interrupt configured with `configure_interrupt`
+    /// and return its source, or fail with `Error::Timeout` after
+    /// `timeout_us`. Reading the source clears a latched interrupt.
+    pub fn wait_for_threshold_event(
+        &mut self,
+        timeout_us: u32,
+    ) -> Result<InterruptSource, Error<E>> {
+        self.route(PinFunction::Threshold)?;
+
+        let Some(pin) = self.pin.as_mut() else {
+            let mut waited = 0;
+            loop {
+                let source = self.driver.interrupt_source()?;
+                if source.triggered {
+                    return Ok(source);
+                }
+                if waited >= timeout_us {
+                    return Err(Error::Timeout);
+                }
+                self.driver.delay.delay_us(DELAY_TIME);
+                waited = waited.saturating_add(DELAY_TIME);
+            }
+        };
+
+        let active_high = self.driver.interrupt_config()?.active_high;
+        wait_for_level(pin, &mut self.driver.delay, active_high, timeout_us)?;
+
+        self.driver.interrupt_source()
+    }
+}
+
+fn wait_for_level<P: InputPin, DELAY: DelayNs, E>(
+    pin: &mut P,
+    delay: &mut DELAY,
+    high: bool,
+    timeout_us: u32,
+) -> Result<(), Error<E>> {
+    let mut waited = 0;
+    while pin.is_high().map_err(|_| Error::Pin)? != high {
+        if waited >= timeout_us {
+            return Err(Error::Timeout);
+        }
+        delay.delay_us(DELAY_TIME);
+        waited = waited.saturating_add(DELAY_TIME);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+impl<IFACE, DELAY, P, E> WithPin<crate::Lis2mdlAsync<IFACE, DELAY>, P>
+where
+    DELAY: embedded_hal_async::delay::DelayNs,
+    IFACE: crate::AsyncInterface<Error = E>,
+    P: embedded_hal_async::digital::Wait,
+{
+    /// Route `function` to the pin (CFG_REG_C), unless it already is.
+    pub async fn route(&mut self, function: PinFunction) -> Result<(), Error<E>> {
+        if self.routing != Some(function) {
+            self.driver
+                .modify_reg::<CfgRegC, _>(|reg| function.route(reg))
+                .await?;
+            self.routing = Some(function);
+        }
+        Ok(())
+    }
+
+    /// Wait for a new sample and read it. Without a pin, STATUS_REG is
+    /// polled every 125 µs.
+    pub async fn wait_for_data_ready(&mut self) -> Result<Measurement, Error<E>> {
+        self.route(PinFunction::DataReady).await?;
+
+        match self.pin.as_mut() {
+            Some(pin) => pin.wait_for_high().await.map_err(|_| Error::Pin)?,
+            None => loop {
+                let measurement = self.driver.read_measurement().await?;
+                if measurement.is_new() {
+                    return Ok(measurement);
+                }
+                self.driver.delay.delay_us(DELAY_TIME).await;
+            },
+        }
+
+        self.driver.read_measurement().await
+    }
+
+    /// Wait for a threshold interrupt and return its source. Reading the
+    /// source clears a latched interrupt.
+    pub async fn wait_for_threshold_event(&mut self) -> Result<InterruptSource, Error<E>> {
+        self.route(PinFunction::Threshold).await?;
+
+        let Some(pin) = self.pin.as_mut() else {
+            loop {
+                let source = self.driver.read_reg::<crate::IntSourceReg>().await?;
+                if source.int() {
+                    return Ok(source.into());
+                }
+                self.driver.delay.delay_us(DELAY_TIME).await;
+            }
+        };
+
+        let ctrl = self.driver.read_reg::<crate::IntCtrlReg>().await?;
+        if ctrl.iea() {
+            pin.wait_for_high().await
+        } else {
+            pin.wait_for_low().await
+        }
+        .map_err(|_| Error::Pin)?;
+
+        self.driver
+            .read_reg::<crate::IntSourceReg>()
+            .await
+            .map(InterruptSource::from)
+    }
+}
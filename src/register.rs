@@ -0,0 +1,319 @@
+// LIS2MDL register map (datasheet DS12144, section 7)
+
+/// Expected value of `WHO_AM_I`.
+pub const CHIP_ID: u8 = 0x40;
+
+/// Register addresses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Register {
+    OffsetXRegL = 0x45,
+    OffsetXRegH = 0x46,
+    OffsetYRegL = 0x47,
+    OffsetYRegH = 0x48,
+    OffsetZRegL = 0x49,
+    OffsetZRegH = 0x4A,
+    WhoAmI = 0x4F,
+    CfgRegA = 0x60,
+    CfgRegB = 0x61,
+    CfgRegC = 0x62,
+    IntCtrlReg = 0x63,
+    IntSourceReg = 0x64,
+    IntThsL = 0x65,
+    IntThsH = 0x66,
+    StatusReg = 0x67,
+    OutxL = 0x68,
+    OutxH = 0x69,
+    OutyL = 0x6A,
+    OutyH = 0x6B,
+    OutzL = 0x6C,
+    OutzH = 0x6D,
+    TempOutL = 0x6E,
+    TempOutH = 0x6F,
+}
+
+impl Register {
+    pub const fn addr(self) -> u8 {
+        self as u8
+    }
+}
+
+impl From<Register> for u8 {
+    fn from(reg: Register) -> u8 {
+        reg.addr()
+    }
+}
+
+/// An 8-bit register whose contents can be decoded into a typed bitfield.
+pub trait RegisterBits: Copy {
+    const REGISTER: Register;
+
+    fn from_bits(bits: u8) -> Self;
+    fn bits(self) -> u8;
+}
+
+/// A register the host may write. Read-only registers such as `STATUS_REG`
+/// only implement [`RegisterBits`].
+pub trait WritableRegister: RegisterBits {}
+
+macro_rules! bitfield_register {
+    (
+        $(#[$meta:meta])*
+        $name:ident: $reg:ident read_only {
+            $( $(#[$fmeta:meta])* $bit:literal => $get:ident; )*
+        }
+    ) => {
+        bitfield_register!(@common $(#[$meta])* $name: $reg);
+
+        impl $name {
+            $(
+                $(#[$fmeta])*
+                pub const fn $get(self) -> bool {
+                    self.0 & (1 << $bit) != 0
+                }
+            )*
+        }
+    };
+    (
+        $(#[$meta:meta])*
+        $name:ident: $reg:ident {
+            $( $(#[$fmeta:meta])* $bit:literal => $get:ident, $with:ident; )*
+        }
+    ) => {
+        bitfield_register!(@common $(#[$meta])* $name: $reg);
+
+        impl $name {
+            $(
+                $(#[$fmeta])*
+                pub const fn $get(self) -> bool {
+                    self.0 & (1 << $bit) != 0
+                }
+
+                $(#[$fmeta])*
+                pub const fn $with(self, value: bool) -> Self {
+                    if value {
+                        $name(self.0 | (1 << $bit))
+                    } else {
+                        $name(self.0 & !(1 << $bit))
+                    }
+                }
+            )*
+        }
+
+        impl WritableRegister for $name {}
+    };
+    (@common $(#[$meta:meta])* $name:ident: $reg:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+        pub struct $name(u8);
+
+        impl $name {
+            pub const fn from_bits(bits: u8) -> Self {
+                $name(bits)
+            }
+
+            pub const fn bits(self) -> u8 {
+                self.0
+            }
+        }
+
+        impl RegisterBits for $name {
+            const REGISTER: Register = Register::$reg;
+
+            fn from_bits(bits: u8) -> Self {
+                $name(bits)
+            }
+
+            fn bits(self) -> u8 {
+                self.0
+            }
+        }
+    };
+}
+
+/// Output data rate, `ODR[1:0]` in `CFG_REG_A`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OutputDataRate {
+    #[default]
+    Hz10 = 0b00,
+    Hz20 = 0b01,
+    Hz50 = 0b10,
+    Hz100 = 0b11,
+}
+
+impl OutputDataRate {
+    pub const fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => OutputDataRate::Hz10,
+            0b01 => OutputDataRate::Hz20,
+            0b10 => OutputDataRate::Hz50,
+            _ => OutputDataRate::Hz100,
+        }
+    }
+
+    pub const fn hz(self) -> u32 {
+        match self {
+            OutputDataRate::Hz10 => 10,
+            OutputDataRate::Hz20 => 20,
+            OutputDataRate::Hz50 => 50,
+            OutputDataRate::Hz100 => 100,
+        }
+    }
+}
+
+/// Operating mode, `MD[1:0]` in `CFG_REG_A`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Mode {
+    #[default]
+    Continuous = 0b00,
+    Single = 0b01,
+    Idle = 0b11,
+}
+
+impl Mode {
+    pub const fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => Mode::Continuous,
+            0b01 => Mode::Single,
+            // 10 and 11 are both idle
+            _ => Mode::Idle,
+        }
+    }
+}
+
+bitfield_register! {
+    /// `CFG_REG_A` (0x60)
+    CfgRegA: CfgRegA {
+        /// Temperature compensation
+        7 => comp_temp_en, with_comp_temp_en;
+        /// Reboot memory content
+        6 => reboot, with_reboot;
+        /// Reset configuration and user registers
+        5 => soft_rst, with_soft_rst;
+        /// Low-power mode
+        4 => lp, with_lp;
+    }
+}
+
+impl CfgRegA {
+    pub const fn odr(self) -> OutputDataRate {
+        OutputDataRate::from_bits(self.0 >> 2)
+    }
+
+    pub const fn with_odr(self, odr: OutputDataRate) -> Self {
+        CfgRegA((self.0 & !0b1100) | ((odr as u8) << 2))
+    }
+
+    pub const fn mode(self) -> Mode {
+        Mode::from_bits(self.0)
+    }
+
+    pub const fn with_mode(self, mode: Mode) -> Self {
+        CfgRegA((self.0 & !0b11) | mode as u8)
+    }
+}
+
+bitfield_register! {
+    /// `CFG_REG_B` (0x61)
+    CfgRegB: CfgRegB {
+        /// Offset cancellation in single measurement mode
+        4 => off_canc_one_shot, with_off_canc_one_shot;
+        /// Interrupt check after hard-iron correction
+        3 => int_on_data_off, with_int_on_data_off;
+        /// Set pulse only at power-on instead of every 63 ODR
+        2 => set_freq, with_set_freq;
+        /// Offset cancellation
+        1 => off_canc, with_off_canc;
+        /// Digital low-pass filter (ODR/4 instead of ODR/2)
+        0 => lpf, with_lpf;
+    }
+}
+
+bitfield_register! {
+    /// `CFG_REG_C` (0x62)
+    CfgRegC: CfgRegC {
+        /// Route the threshold interrupt to the INT/DRDY pin
+        6 => int_on_pin, with_int_on_pin;
+        /// Disable the I²C interface
+        5 => i2c_dis, with_i2c_dis;
+        /// Block data update
+        4 => bdu, with_bdu;
+        /// Swap high and low data bytes
+        3 => ble, with_ble;
+        /// Enable 4-wire SPI
+        2 => four_wire_spi, with_four_wire_spi;
+        /// Self-test
+        1 => self_test, with_self_test;
+        /// Route data-ready to the INT/DRDY pin
+        0 => drdy_on_pin, with_drdy_on_pin;
+    }
+}
+
+bitfield_register! {
+    /// `INT_CTRL_REG` (0x63)
+    IntCtrlReg: IntCtrlReg {
+        /// X-axis interrupt
+        7 => xien, with_xien;
+        /// Y-axis interrupt
+        6 => yien, with_yien;
+        /// Z-axis interrupt
+        5 => zien, with_zien;
+        /// Interrupt active high
+        2 => iea, with_iea;
+        /// Interrupt latched until INT_SOURCE_REG is read
+        1 => iel, with_iel;
+        /// Interrupt enable
+        0 => ien, with_ien;
+    }
+}
+
+bitfield_register! {
+    /// `INT_SOURCE_REG` (0x64), read-only
+    IntSourceReg: IntSourceReg read_only {
+        /// X-axis exceeded the positive threshold
+        7 => p_th_s_x;
+        /// Y-axis exceeded the positive threshold
+        6 => p_th_s_y;
+        /// Z-axis exceeded the positive threshold
+        5 => p_th_s_z;
+        /// X-axis exceeded the negative threshold
+        4 => n_th_s_x;
+        /// Y-axis exceeded the negative threshold
+        3 => n_th_s_y;
+        /// Z-axis exceeded the negative threshold
+        2 => n_th_s_z;
+        /// Internal measurement range overflow
+        1 => mroi;
+        /// Interrupt event
+        0 => int;
+    }
+}
+
+bitfield_register! {
+    /// `STATUS_REG` (0x67), read-only
+    StatusReg: StatusReg read_only {
+        /// X, Y and Z data overrun
+        7 => zyxor;
+        /// Z-axis data overrun
+        6 => zor;
+        /// Y-axis data overrun
+        5 => yor;
+        /// X-axis data overrun
+        4 => xor;
+        /// X, Y and Z new data available
+        3 => zyxda;
+        /// Z-axis new data available
+        2 => zda;
+        /// Y-axis new data available
+        1 => yda;
+        /// X-axis new data available
+        0 => xda;
+    }
+}
+
+impl StatusReg {
+    /// Any axis overran (a sample was overwritten before being read).
+    pub const fn overrun(self) -> bool {
+        self.0 & 0xF0 != 0
+    }
+}
@@ -0,0 +1,515 @@
+// Simulated LIS2MDL for host-side tests: a register file behind
+// `embedded_hal` I²C and SPI implementations, with simulated time advanced
+// by a matching delay.
+
+extern crate std;
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use std::f64::consts::TAU;
+use std::rc::Rc;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, InputPin};
+use embedded_hal::i2c::{self, I2c, NoAcknowledgeSource};
+use embedded_hal::spi::{self, SpiDevice};
+
+use crate::DEFAULT_DEVICE_ID;
+use crate::interface::SPI_READ;
+use crate::register::{
+    CHIP_ID, CfgRegA, CfgRegB, CfgRegC, IntCtrlReg, Mode, Register, RegisterBits,
+};
+
+/// Output change added while the Self_test bit is set, in LSB. Inside the
+/// datasheet's 15..500 LSB pass window.
+pub const SELF_TEST_DELTA: [i16; 3] = [100, 100, 200];
+
+const MICROTESLA_PER_LSB: f64 = 0.15;
+const OUTPUT: core::ops::Range<usize> = 0x68..0x70;
+
+/// Field seen by the simulated sensor, in µT along the chip axes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Field {
+    Constant([f32; 3]),
+    /// `horizontal` µT turning from +X towards +Y once every `period_ms`,
+    /// plus a fixed `vertical` Z component.
+    Rotating {
+        horizontal: f32,
+        vertical: f32,
+        period_ms: u32,
+    },
+}
+
+impl Default for Field {
+    fn default() -> Self {
+        Field::Constant([0.0; 3])
+    }
+}
+
+impl Field {
+    /// The field at `time_ns` after power-on.
+    pub fn at(&self, time_ns: u64) -> [f32; 3] {
+        match *self {
+            Field::Constant(field) => field,
+            Field::Rotating {
+                horizontal,
+                vertical,
+                period_ms,
+            } => {
+                let turns = time_ns as f64 / (period_ms.max(1) as f64 * 1e6);
+                let angle = TAU * turns.fract();
+                [
+                    (horizontal as f64 * angle.cos()) as f32,
+                    (horizontal as f64 * angle.sin()) as f32,
+                    vertical,
+                ]
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Device {
+    regs: [u8; 0x80],
+    pointer: u8,
+    now_ns: u64,
+    next_sample_ns: Option<u64>,
+    field: Field,
+    noise: f32,
+    rng: u32,
+    temperature: f32,
+    sensor_offset: [i16; 3],
+    // BDU: a low output byte was read and its high byte not yet
+    bdu_locked: bool,
+    pending: Option<[u8; 8]>,
+    high_byte_read: bool,
+}
+
+impl Device {
+    fn new() -> Self {
+        let mut device = Device {
+            regs: [0; 0x80],
+            pointer: 0,
+            now_ns: 0,
+            next_sample_ns: None,
+            field: Field::default(),
+            noise: 0.0,
+            rng: 1,
+            temperature: 25.0,
+            sensor_offset: [0; 3],
+            bdu_locked: false,
+            pending: None,
+            high_byte_read: false,
+        };
+        device.regs[Register::WhoAmI.addr() as usize] = CHIP_ID;
+        device.soft_reset();
+        device
+    }
+
+    fn reg<R: RegisterBits>(&self) -> R {
+        R::from_bits(self.regs[R::REGISTER.addr() as usize])
+    }
+
+    // SOFT_RST clears the configuration and user registers
+    fn soft_reset(&mut self) {
+        for reg in (0x45..=0x4A).chain(0x60..=0x6F) {
+            self.regs[reg] = 0;
+        }
+        self.regs[Register::CfgRegA.addr() as usize] =
+            CfgRegA::default().with_mode(Mode::Idle).bits();
+        self.next_sample_ns = None;
+        self.bdu_locked = false;
+        self.pending = None;
+    }
+
+    fn sample_period_ns(&self) -> u64 {
+        1_000_000_000 / self.reg::<CfgRegA>().odr().hz() as u64
+    }
+
+    fn advance(&mut self, ns: u64) {
+        self.now_ns += ns;
+        while let Some(time) = self.next_sample_ns.filter(|&time| time <= self.now_ns) {
+            self.sample(time);
+
+            let a = self.reg::<CfgRegA>();
+            self.next_sample_ns = match a.mode() {
+                Mode::Continuous => Some(time + self.sample_period_ns()),
+                _ => {
+                    // single mode returns to idle after one conversion
+                    self.regs[Register::CfgRegA.addr() as usize] = a.with_mode(Mode::Idle).bits();
+                    None
+                }
+            };
+        }
+    }
+
+    fn next_noise(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let uniform = (self.rng >> 8) as f64 / (1u32 << 24) as f64;
+        (uniform * 2.0 - 1.0) * self.noise as f64
+    }
+
+    fn sample(&mut self, time_ns: u64) {
+        let field = self.field.at(time_ns);
+        let self_test = self.reg::<CfgRegC>().self_test();
+        // set/reset averaging removes the sensor's own offset
+        let b = self.reg::<CfgRegB>();
+        let cancelled = b.off_canc()
+            && (self.reg::<CfgRegA>().mode() == Mode::Continuous || b.off_canc_one_shot());
+
+        let mut raw = [0i32; 3];
+        let mut corrected = [0i32; 3];
+        for axis in 0..3 {
+            let microtesla = field[axis] as f64 + self.next_noise();
+            raw[axis] = (microtesla / MICROTESLA_PER_LSB).round() as i32;
+            if self_test {
+                raw[axis] += SELF_TEST_DELTA[axis] as i32;
+            }
+            if !cancelled {
+                raw[axis] += self.sensor_offset[axis] as i32;
+            }
+
+            let offset = 0x45 + 2 * axis;
+            let offset = i16::from_le_bytes([self.regs[offset], self.regs[offset + 1]]);
+            corrected[axis] = raw[axis] - offset as i32;
+        }
+
+        let mut output = [0u8; 8];
+        for (chunk, value) in output.chunks_exact_mut(2).zip(corrected) {
+            let value = value.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        let temperature = ((self.temperature - 25.0) * 8.0).round() as i16;
+        output[6..].copy_from_slice(&temperature.to_le_bytes());
+
+        if self.bdu_locked {
+            self.pending = Some(output);
+        } else {
+            self.regs[OUTPUT].copy_from_slice(&output);
+        }
+
+        let status = &mut self.regs[Register::StatusReg.addr() as usize];
+        if *status & 0x08 != 0 {
+            *status |= 0xF0;
+        }
+        *status |= 0x0F;
+
+        let checked = if self.reg::<CfgRegB>().int_on_data_off() {
+            corrected
+        } else {
+            raw
+        };
+        self.check_threshold(checked);
+    }
+
+    fn check_threshold(&mut self, xyz: [i32; 3]) {
+        let ctrl = self.reg::<IntCtrlReg>();
+        let threshold = u16::from_le_bytes([self.regs[0x65], self.regs[0x66]]) & 0x7FFF;
+        let source = &mut self.regs[Register::IntSourceReg.addr() as usize];
+        if !ctrl.ien() {
+            *source = 0;
+            return;
+        }
+
+        let enabled = [ctrl.xien(), ctrl.yien(), ctrl.zien()];
+        let mut events = 0u8;
+        for axis in 0..3 {
+            if !enabled[axis] {
+                continue;
+            }
+            if xyz[axis] > threshold as i32 {
+                events |= 0x80 >> axis;
+            } else if xyz[axis] < -(threshold as i32) {
+                events |= 0x10 >> axis;
+            }
+        }
+        if events != 0 {
+            events |= 0x01;
+        }
+
+        if ctrl.iel() {
+            *source |= events;
+        } else {
+            *source = events;
+        }
+    }
+
+    fn read(&mut self) -> u8 {
+        let reg = self.pointer as usize;
+        self.pointer = (self.pointer + 1) & 0x7F;
+        let value = self.regs[reg];
+
+        match reg {
+            0x64 if self.reg::<IntCtrlReg>().iel() => self.regs[reg] = 0,
+            0x68..=0x6F => {
+                if reg < 0x6E {
+                    // reading the output clears ZYXDA and the overrun flags
+                    self.regs[Register::StatusReg.addr() as usize] = 0;
+                }
+                if reg & 1 == 0 {
+                    self.bdu_locked = self.reg::<CfgRegC>().bdu();
+                } else {
+                    self.high_byte_read = true;
+                }
+            }
+            _ => {}
+        }
+
+        value
+    }
+
+    fn write(&mut self, value: u8) {
+        let reg = self.pointer as usize;
+        self.pointer = (self.pointer + 1) & 0x7F;
+
+        match reg {
+            0x45..=0x4A | 0x61..=0x63 | 0x65 | 0x66 => self.regs[reg] = value,
+            0x60 => {
+                let a = CfgRegA::from_bits(value);
+                if a.soft_rst() {
+                    self.soft_reset();
+                    return;
+                }
+                // REBOOT and SOFT_RST clear themselves
+                let a = a.with_reboot(false);
+                self.regs[reg] = a.bits();
+                self.next_sample_ns = match a.mode() {
+                    Mode::Idle => None,
+                    _ => Some(self.now_ns + self.sample_period_ns()),
+                };
+            }
+            // read-only
+            _ => {}
+        }
+    }
+
+    // INT/DRDY level: DRDY is active high, INT follows IEA
+    fn pin_level(&self) -> bool {
+        let c = self.reg::<CfgRegC>();
+        let data_ready =
+            c.drdy_on_pin() && self.regs[Register::StatusReg.addr() as usize] & 0x08 != 0;
+        let interrupt = c.int_on_pin() && {
+            let triggered = self.regs[Register::IntSourceReg.addr() as usize] & 0x01 != 0;
+            triggered == self.reg::<IntCtrlReg>().iea()
+        };
+        data_ready || interrupt
+    }
+
+    fn end_access(&mut self) {
+        if core::mem::take(&mut self.high_byte_read) {
+            self.bdu_locked = false;
+            if let Some(output) = self.pending.take() {
+                self.regs[OUTPUT].copy_from_slice(&output);
+            }
+        }
+    }
+}
+
+/// A simulated LIS2MDL. Clones share the same device, so one handle can be
+/// given to the driver as its bus while the test keeps another.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    device: Rc<RefCell<Device>>,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Simulator::new()
+    }
+}
+
+impl Simulator {
+    /// A powered-on device in idle mode with a zero field.
+    pub fn new() -> Self {
+        Simulator {
+            device: Rc::new(RefCell::new(Device::new())),
+        }
+    }
+
+    /// A delay that advances simulated time instead of sleeping.
+    pub fn delay(&self) -> SimDelay {
+        SimDelay {
+            device: self.device.clone(),
+        }
+    }
+
+    pub fn set_field(&self, field: Field) {
+        self.device.borrow_mut().field = field;
+    }
+
+    /// Add uniform noise of up to ±`amplitude` µT per axis, from a
+    /// deterministic generator seeded with `seed`.
+    pub fn set_noise(&self, amplitude: f32, seed: u32) {
+        let mut device = self.device.borrow_mut();
+        device.noise = amplitude;
+        device.rng = seed;
+    }
+
+    /// The sensor's own offset in LSB, present in the output unless offset
+    /// cancellation is on (with OFF_CANC_ONE_SHOT in single mode).
+    pub fn set_sensor_offset(&self, offset: [i16; 3]) {
+        self.device.borrow_mut().sensor_offset = offset;
+    }
+
+    /// Die temperature in °C reported from the next sample on.
+    pub fn set_temperature(&self, celsius: f32) {
+        self.device.borrow_mut().temperature = celsius;
+    }
+
+    /// Advance simulated time, producing any samples that fall due.
+    pub fn advance_ns(&self, ns: u64) {
+        self.device.borrow_mut().advance(ns);
+    }
+
+    pub fn now_ns(&self) -> u64 {
+        self.device.borrow().now_ns
+    }
+
+    /// The INT/DRDY pin.
+    pub fn pin(&self) -> SimPin {
+        SimPin {
+            device: self.device.clone(),
+        }
+    }
+
+    /// Register contents without the side effects of a bus read.
+    pub fn peek(&self, reg: u8) -> u8 {
+        self.device.borrow().regs[(reg & 0x7F) as usize]
+    }
+
+    /// Overwrite a register directly, including read-only ones.
+    pub fn poke(&self, reg: u8, value: u8) {
+        self.device.borrow_mut().regs[(reg & 0x7F) as usize] = value;
+    }
+}
+
+impl i2c::ErrorType for Simulator {
+    type Error = i2c::ErrorKind;
+}
+
+impl I2c for Simulator {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut device = self.device.borrow_mut();
+        if address != DEFAULT_DEVICE_ID || device.reg::<CfgRegC>().i2c_dis() {
+            return Err(i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+
+        // the first byte after a (repeated) start in write direction is
+        // the register address
+        let mut expect_address = true;
+        for operation in operations {
+            match operation {
+                i2c::Operation::Write(bytes) => {
+                    for &byte in bytes.iter() {
+                        if expect_address {
+                            device.pointer = byte & 0x7F;
+                            expect_address = false;
+                        } else {
+                            device.write(byte);
+                        }
+                    }
+                }
+                i2c::Operation::Read(buffer) => {
+                    buffer.fill_with(|| device.read());
+                    expect_address = true;
+                }
+            }
+        }
+        device.end_access();
+
+        Ok(())
+    }
+}
+
+impl spi::ErrorType for Simulator {
+    type Error = spi::ErrorKind;
+}
+
+impl SpiDevice for Simulator {
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let mut address = None;
+        // one byte on the wire; returns what the device drives back
+        let mut transfer = |device: &mut Device, out: u8| match address {
+            None => {
+                device.pointer = out & 0x7F;
+                address = Some(out);
+                0
+            }
+            Some(address) if address & SPI_READ != 0 => device.read(),
+            Some(_) => {
+                device.write(out);
+                0
+            }
+        };
+
+        for operation in operations {
+            let mut device = self.device.borrow_mut();
+            match operation {
+                spi::Operation::Write(bytes) => {
+                    for &byte in bytes.iter() {
+                        transfer(&mut device, byte);
+                    }
+                }
+                spi::Operation::Read(buffer) => {
+                    buffer.fill_with(|| transfer(&mut device, 0));
+                }
+                spi::Operation::Transfer(read, write) => {
+                    for i in 0..read.len().max(write.len()) {
+                        let value = transfer(&mut device, write.get(i).copied().unwrap_or(0));
+                        if let Some(slot) = read.get_mut(i) {
+                            *slot = value;
+                        }
+                    }
+                }
+                spi::Operation::TransferInPlace(buffer) => {
+                    for byte in buffer.iter_mut() {
+                        *byte = transfer(&mut device, *byte);
+                    }
+                }
+                spi::Operation::DelayNs(ns) => device.advance(*ns as u64),
+            }
+        }
+        self.device.borrow_mut().end_access();
+
+        Ok(())
+    }
+}
+
+/// Delay for use with a [`Simulator`]; advances its clock.
+#[derive(Debug, Clone)]
+pub struct SimDelay {
+    device: Rc<RefCell<Device>>,
+}
+
+impl DelayNs for SimDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.device.borrow_mut().advance(ns as u64);
+    }
+}
+
+/// INT/DRDY output of a [`Simulator`].
+#[derive(Debug, Clone)]
+pub struct SimPin {
+    device: Rc<RefCell<Device>>,
+}
+
+impl digital::ErrorType for SimPin {
+    type Error = Infallible;
+}
+
+impl InputPin for SimPin {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.device.borrow().pin_level())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        self.is_high().map(|high| !high)
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{LIS2MDL_MAG_LSB, LIS2MDL_MILLIGAUSS_TO_MICROTESLA};
+
+/// A magnetic field vector.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct MagneticField {
+    microtesla: (f32, f32, f32),
+}
+
+impl MagneticField {
+    pub const fn from_microtesla(x: f32, y: f32, z: f32) -> Self {
+        MagneticField {
+            microtesla: (x, y, z),
+        }
+    }
+
+    pub fn from_milligauss(x: f32, y: f32, z: f32) -> Self {
+        let scale = LIS2MDL_MILLIGAUSS_TO_MICROTESLA;
+        Self::from_microtesla(x * scale, y * scale, z * scale)
+    }
+
+    /// From raw output at 1.5 mG/LSB.
+    pub fn from_raw(raw: (i16, i16, i16)) -> Self {
+        Self::from_milligauss(
+            raw.0 as f32 * LIS2MDL_MAG_LSB,
+            raw.1 as f32 * LIS2MDL_MAG_LSB,
+            raw.2 as f32 * LIS2MDL_MAG_LSB,
+        )
+    }
+
+    pub const fn microtesla(&self) -> (f32, f32, f32) {
+        self.microtesla
+    }
+
+    pub fn milligauss(&self) -> (f32, f32, f32) {
+        self.scaled(1.0 / LIS2MDL_MILLIGAUSS_TO_MICROTESLA)
+    }
+
+    pub fn gauss(&self) -> (f32, f32, f32) {
+        self.scaled(1.0 / (LIS2MDL_MILLIGAUSS_TO_MICROTESLA * 1000.0))
+    }
+
+    /// In sensor LSB (1.5 mG). Not rounded, since calibrated fields are
+    /// rarely whole LSBs.
+    pub fn raw_lsb(&self) -> (f32, f32, f32) {
+        self.scaled(1.0 / (LIS2MDL_MILLIGAUSS_TO_MICROTESLA * LIS2MDL_MAG_LSB))
+    }
+
+    fn scaled(&self, factor: f32) -> (f32, f32, f32) {
+        let (x, y, z) = self.microtesla;
+        (x * factor, y * factor, z * factor)
+    }
+
+    /// Field strength in µT.
+    pub fn magnitude_microtesla(&self) -> f32 {
+        let (x, y, z) = self.microtesla;
+        crate::calibration::sqrt((x * x + y * y + z * z) as f64) as f32
+    }
+
+    #[cfg(feature = "uom")]
+    pub fn to_uom(&self) -> [uom::si::f32::MagneticFluxDensity; 3] {
+        use uom::si::magnetic_flux_density::microtesla;
+
+        let (x, y, z) = self.microtesla;
+        [x, y, z].map(uom::si::f32::MagneticFluxDensity::new::<microtesla>)
+    }
+}
+
+/// 8-point compass direction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Cardinal {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl Cardinal {
+    pub const fn abbreviation(self) -> &'static str {
+        match self {
+            Cardinal::N => "N",
+            Cardinal::NE => "NE",
+            Cardinal::E => "E",
+            Cardinal::SE => "SE",
+            Cardinal::S => "S",
+            Cardinal::SW => "SW",
+            Cardinal::W => "W",
+            Cardinal::NW => "NW",
+        }
+    }
+}
+
+/// A compass heading, normalized to [0, 360) degrees.
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Heading {
+    degrees: f32,
+}
+
+impl Heading {
+    pub fn from_degrees(degrees: f32) -> Self {
+        let degrees = degrees % 360.0;
+        Heading {
+            degrees: if degrees < 0.0 {
+                degrees + 360.0
+            } else {
+                degrees
+            },
+        }
+    }
+
+    pub fn from_radians(radians: f32) -> Self {
+        Self::from_degrees(radians.to_degrees())
+    }
+
+    pub const fn degrees(&self) -> f32 {
+        self.degrees
+    }
+
+    pub fn radians(&self) -> f32 {
+        self.degrees.to_radians()
+    }
+
+    /// Nearest of the 8 compass points.
+    pub fn cardinal(&self) -> Cardinal {
+        const POINTS: [Cardinal; 8] = [
+            Cardinal::N,
+            Cardinal::NE,
+            Cardinal::E,
+            Cardinal::SE,
+            Cardinal::S,
+            Cardinal::SW,
+            Cardinal::W,
+            Cardinal::NW,
+        ];
+        POINTS[((self.degrees + 22.5) / 45.0) as usize % 8]
+    }
+
+    #[cfg(feature = "uom")]
+    pub fn to_uom(&self) -> uom::si::f32::Angle {
+        uom::si::f32::Angle::new::<uom::si::angle::degree>(self.degrees)
+    }
+}
@@ -0,0 +1,216 @@
+// Magnetic declination from a spherical harmonic main field model (World
+// Magnetic Model form: Schmidt semi-normalized Gauss coefficients with
+// linear secular variation), evaluated on a spherical Earth at sea level.
+
+#[allow(unused_imports)] // float methods resolve to std when testing
+use micromath::F32Ext;
+
+use crate::calibration::sqrt;
+
+/// One Gauss coefficient pair in nT, with secular variation in nT/year.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Coefficient {
+    pub n: u8,
+    pub m: u8,
+    pub g: f32,
+    pub h: f32,
+    pub g_dot: f32,
+    pub h_dot: f32,
+}
+
+/// A main field model: epoch (decimal year) and coefficients ordered by
+/// degree `n`, then order `m`, starting at n = 1.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Model {
+    pub epoch: f32,
+    pub max_degree: u8,
+    pub coefficients: &'static [Coefficient],
+}
+
+const fn c(n: u8, m: u8, g: f32, h: f32, g_dot: f32, h_dot: f32) -> Coefficient {
+    Coefficient {
+        n,
+        m,
+        g,
+        h,
+        g_dot,
+        h_dot,
+    }
+}
+
+/// WMM2025 (valid 2025.0–2030.0), the full degree and order 12 model.
+///
+/// Away from the poles the spherical-Earth evaluation stays within a few
+/// tenths of a degree of the official calculator. Local crustal anomalies
+/// are not part of the model.
+pub const WMM2025: Model = Model {
+    epoch: 2025.0,
+    max_degree: 12,
+    coefficients: &[
+        c(1, 0, -29351.8, 0.0, 12.0, 0.0),
+        c(1, 1, -1410.8, 4545.4, 9.7, -21.5),
+        c(2, 0, -2556.6, 0.0, -11.6, 0.0),
+        c(2, 1, 2951.1, -3133.6, -5.2, -27.7),
+        c(2, 2, 1649.3, -815.1, -8.0, -12.1),
+        c(3, 0, 1361.0, 0.0, -1.3, 0.0),
+        c(3, 1, -2404.1, -56.6, -4.2, 4.0),
+        c(3, 2, 1243.8, 237.5, 0.4, -0.3),
+        c(3, 3, 453.6, -549.5, -15.6, -4.1),
+        c(4, 0, 895.0, 0.0, -1.6, 0.0),
+        c(4, 1, 799.5, 278.6, -2.4, -1.1),
+        c(4, 2, 55.7, -133.9, -6.0, 4.1),
+        c(4, 3, -281.1, 212.0, 5.6, 1.6),
+        c(4, 4, 12.1, -375.6, -7.0, -4.4),
+        c(5, 0, -233.2, 0.0, 0.6, 0.0),
+        c(5, 1, 368.9, 45.4, 1.4, -0.5),
+        c(5, 2, 187.2, 220.2, 0.0, 2.2),
+        c(5, 3, -138.7, -122.9, 0.6, 0.4),
+        c(5, 4, -142.0, 43.0, 2.2, 1.7),
+        c(5, 5, 20.9, 106.1, 0.9, 1.9),
+        c(6, 0, 64.4, 0.0, -0.2, 0.0),
+        c(6, 1, 63.8, -18.4, -0.4, 0.3),
+        c(6, 2, 76.9, 16.8, 0.9, -1.6),
+        c(6, 3, -115.7, 48.8, 1.2, -0.4),
+        c(6, 4, -40.9, -59.8, -0.9, 0.9),
+        c(6, 5, 14.9, 10.9, 0.3, 0.7),
+        c(6, 6, -60.7, 72.7, 0.9, 0.9),
+        c(7, 0, 79.5, 0.0, 0.0, 0.0),
+        c(7, 1, -77.0, -48.9, -0.1, 0.6),
+        c(7, 2, -8.8, -14.4, -0.1, 0.5),
+        c(7, 3, 59.3, -1.0, 0.5, -0.8),
+        c(7, 4, 15.8, 23.4, -0.1, 0.0),
+        c(7, 5, 2.5, -7.4, -0.8, -1.0),
+        c(7, 6, -11.1, -25.1, -0.8, 0.6),
+        c(7, 7, 14.2, -2.3, 0.8, -0.2),
+        c(8, 0, 23.2, 0.0, -0.1, 0.0),
+        c(8, 1, 10.8, 7.1, 0.2, -0.2),
+        c(8, 2, -17.5, -12.6, 0.0, 0.5),
+        c(8, 3, 2.0, 11.4, 0.5, -0.4),
+        c(8, 4, -21.7, -9.7, -0.1, 0.4),
+        c(8, 5, 16.9, 12.7, 0.3, -0.5),
+        c(8, 6, 15.0, 0.7, 0.2, -0.6),
+        c(8, 7, -16.8, -5.2, 0.0, 0.3),
+        c(8, 8, 0.9, 3.9, 0.2, 0.2),
+        c(9, 0, 4.6, 0.0, 0.0, 0.0),
+        c(9, 1, 7.8, -24.8, -0.1, -0.3),
+        c(9, 2, 3.0, 12.2, 0.1, 0.3),
+        c(9, 3, -0.2, 8.3, 0.3, -0.3),
+        c(9, 4, -2.5, -3.3, -0.3, 0.3),
+        c(9, 5, -13.1, -5.2, 0.0, 0.2),
+        c(9, 6, 2.4, 7.2, 0.3, -0.1),
+        c(9, 7, 8.6, -0.6, -0.1, -0.2),
+        c(9, 8, -8.7, 0.8, 0.1, 0.4),
+        c(9, 9, -12.9, 10.0, -0.1, 0.1),
+        c(10, 0, -1.3, 0.0, 0.1, 0.0),
+        c(10, 1, -6.4, 3.3, 0.0, 0.0),
+        c(10, 2, 0.2, 0.0, 0.1, 0.0),
+        c(10, 3, 2.0, 2.4, 0.1, -0.2),
+        c(10, 4, -1.0, 5.3, 0.0, 0.1),
+        c(10, 5, -0.6, -9.1, -0.3, -0.1),
+        c(10, 6, -0.9, 0.4, 0.0, 0.1),
+        c(10, 7, 1.5, -4.2, -0.1, 0.0),
+        c(10, 8, 0.9, -3.8, -0.1, -0.1),
+        c(10, 9, -2.7, 0.9, 0.0, 0.2),
+        c(10, 10, -3.9, -9.1, 0.0, 0.0),
+        c(11, 0, 2.9, 0.0, 0.0, 0.0),
+        c(11, 1, -1.5, 0.0, 0.0, 0.0),
+        c(11, 2, -2.5, 2.9, 0.0, 0.1),
+        c(11, 3, 2.4, -0.6, 0.0, 0.0),
+        c(11, 4, -0.6, 0.2, 0.0, 0.1),
+        c(11, 5, -0.1, 0.5, -0.1, 0.0),
+        c(11, 6, -0.6, -0.3, 0.0, 0.0),
+        c(11, 7, -0.1, -1.2, 0.0, 0.1),
+        c(11, 8, 1.1, -1.7, -0.1, 0.0),
+        c(11, 9, -1.0, -2.9, -0.1, 0.0),
+        c(11, 10, -0.2, -1.8, -0.1, 0.0),
+        c(11, 11, 2.6, -2.3, -0.1, 0.0),
+        c(12, 0, -2.0, 0.0, 0.0, 0.0),
+        c(12, 1, -0.2, -1.3, 0.0, 0.0),
+        c(12, 2, 0.3, 0.7, 0.0, 0.0),
+        c(12, 3, 1.2, 1.0, 0.0, -0.1),
+        c(12, 4, -1.3, -1.4, 0.0, 0.1),
+        c(12, 5, 0.6, 0.0, 0.0, 0.0),
+        c(12, 6, 0.6, 0.6, 0.1, 0.0),
+        c(12, 7, 0.5, -0.1, 0.0, 0.0),
+        c(12, 8, -0.1, 0.8, 0.0, 0.0),
+        c(12, 9, -0.4, 0.1, 0.0, 0.0),
+        c(12, 10, -0.2, -1.0, -0.1, 0.0),
+        c(12, 11, -1.3, 0.1, 0.0, 0.0),
+        c(12, 12, -0.7, 0.2, -0.1, -0.1),
+    ],
+};
+
+// Highest degree `Model::field` can evaluate
+const MAX_DEGREE: usize = 12;
+
+impl Model {
+    /// North and east components of the main field in nT at sea level.
+    /// Latitude and longitude in degrees, `year` as a decimal year.
+    pub fn field(&self, latitude: f32, longitude: f32, year: f32) -> (f32, f32) {
+        let dt = year - self.epoch;
+        let degree = (self.max_degree as usize).min(MAX_DEGREE);
+
+        // keep away from the poles, where the east component is singular
+        let colatitude = (90.0 - latitude).clamp(0.01, 179.99).to_radians();
+        let (sin_t, cos_t) = (colatitude.sin() as f64, colatitude.cos() as f64);
+        let lon = longitude.to_radians();
+
+        // Unnormalized associated Legendre functions P[n][m](cos θ) without
+        // the Condon-Shortley phase
+        let mut p = [[0.0f64; MAX_DEGREE + 1]; MAX_DEGREE + 1];
+        p[0][0] = 1.0;
+        for m in 0..=degree {
+            if m > 0 {
+                p[m][m] = (2 * m - 1) as f64 * sin_t * p[m - 1][m - 1];
+            }
+            if m < degree {
+                p[m + 1][m] = (2 * m + 1) as f64 * cos_t * p[m][m];
+            }
+            for n in m + 2..=degree {
+                p[n][m] = ((2 * n - 1) as f64 * cos_t * p[n - 1][m]
+                    - (n + m - 1) as f64 * p[n - 2][m])
+                    / (n - m) as f64;
+            }
+        }
+
+        let (mut north, mut east) = (0.0f64, 0.0f64);
+        for coef in self.coefficients {
+            let (n, m) = (coef.n as usize, coef.m as usize);
+            if n == 0 || n > degree || m > n {
+                continue;
+            }
+
+            // Schmidt semi-normalization: √(2 (n-m)! / (n+m)!) for m > 0
+            let mut schmidt = 1.0f64;
+            if m > 0 {
+                let mut ratio = 2.0;
+                for k in n - m + 1..=n + m {
+                    ratio /= k as f64;
+                }
+                schmidt = sqrt(ratio);
+            }
+
+            let p_nm = schmidt * p[n][m];
+            let p_prev = if n > m { schmidt * p[n - 1][m] } else { 0.0 };
+            // dP/dθ from (1 - x²) dP/dx = (n + m) P[n-1][m] - n x P[n][m]
+            let dp_nm = (n as f64 * cos_t * p_nm - (n + m) as f64 * p_prev) / sin_t;
+
+            let g = (coef.g + dt * coef.g_dot) as f64;
+            let h = (coef.h + dt * coef.h_dot) as f64;
+            let angle = m as f32 * lon;
+            let (sin_ml, cos_ml) = (angle.sin() as f64, angle.cos() as f64);
+
+            north += (g * cos_ml + h * sin_ml) * dp_nm;
+            east += m as f64 * (g * sin_ml - h * cos_ml) * p_nm / sin_t;
+        }
+
+        (north as f32, east as f32)
+    }
+
+    /// Declination in degrees, positive when magnetic north is east of true
+    /// north.
+    pub fn declination(&self, latitude: f32, longitude: f32, year: f32) -> f32 {
+        let (north, east) = self.field(latitude, longitude, year);
+        east.atan2(north).to_degrees()
+    }
+}